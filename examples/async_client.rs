@@ -8,11 +8,8 @@ use tibrv::context::{RvCtx, TransportBuilder};
 use tibrv::field::Decodable;
 use tibrv::message::Msg;
 
-use tokio::prelude::*;
-use tokio::reactor::Handle;
-
-fn main() {
-    let handle = Handle::default();
+#[tokio::main]
+async fn main() {
     let ctx = RvCtx::new().unwrap(); // Create the context, starting Rendezvous internals
     let tp = TransportBuilder::new(ctx.clone())
         .create()
@@ -21,16 +18,11 @@ fn main() {
     let mut msg = Msg::new().unwrap();
     msg.set_send_subject("TEST.SUBJECT").unwrap();
 
-    let response = tp.async_req(&handle, &mut msg).unwrap();
-
-    let events = response.then(|msg| {
-        let unwrapped = msg.unwrap();
-        let reply = unwrapped.get_field_by_name("reply").unwrap();
-        let decoded = <&CStr>::tibrv_try_decode(&reply).unwrap();
+    let response = tp.async_req(&mut msg).unwrap();
 
-        println!("{:?}", decoded);
-        Ok(())
-    });
+    let unwrapped = response.await.unwrap();
+    let reply = unwrapped.get_field_by_name("reply").unwrap();
+    let decoded = <&CStr>::tibrv_try_decode(&reply).unwrap();
 
-    tokio::run(events)
+    println!("{:?}", decoded);
 }
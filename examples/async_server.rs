@@ -4,31 +4,30 @@ extern crate tokio;
 
 use tibrv::context::{RvCtx, TransportBuilder};
 
+use futures::future;
 use std::ffi::CString;
 use tibrv::field::Builder;
-use tokio::prelude::*;
-use tokio::reactor::Handle;
 
 /// This example shows how to subscribe to incoming messages using
 /// a Futures `Stream`, chain some work on each incoming message,
-/// then forward some response (in this case just echoing the message)
-/// back via a `Sink`, implemented by the `Transport`.
-fn main() {
-    let handle = Handle::default(); // Get a handle to the default reactor
+/// then send some response (in this case just echoing the message)
+/// back as a reply to each request.
+#[tokio::main]
+async fn main() {
     let ctx = RvCtx::new().expect("Couldn't start tibrv context");
     let tp = TransportBuilder::new(ctx.clone())
         .create()
         .expect("Couldn't create default transport.");
 
     // Set up the incoming event stream
-    let events =
-        tp.async_serve(&handle, "TEST.SUBJECT", |mut msg| {
-            let data = CString::new("Reply!").unwrap();
-            let mut field = Builder::new(&data.as_c_str()).with_name("reply").encode();
-            let _ = msg.add_field(&mut field).unwrap();
-            Ok(msg)
-        }).then(|_| Ok(()));
+    let events = tp.async_serve("TEST.SUBJECT", |msg| {
+        let mut reply = msg.try_clone().unwrap();
+        let data = CString::new("Reply!").unwrap();
+        let mut field = Builder::new(&data.as_c_str()).with_name("reply").encode();
+        let _ = reply.add_field(&mut field).unwrap();
+        future::ready(Ok(reply))
+    });
 
     // Finally, run the event loop.
-    tokio::run(events);
+    let _ = events.await;
 }
@@ -11,10 +11,12 @@ fn main() {
         .create()
         .expect("Couldn't create default transport");
 
-    tp.serve("TEST.SUBJECT", |mut msg| {
+    tp.serve("TEST.SUBJECT", |msg| {
+        let mut reply = msg.try_clone().unwrap();
         let data = CString::new("Reply!").unwrap();
         let mut field = Builder::new(&data.as_c_str()).with_name("reply").encode();
-        let _ = msg.add_field(&mut field).unwrap();
-        Ok(msg)
-    }).unwrap();
+        let _ = reply.add_field(&mut field).unwrap();
+        Ok(reply)
+    })
+    .unwrap();
 }
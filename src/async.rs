@@ -1,25 +1,33 @@
-//! Asynchronous interfaces for integrating Rendezvous with Tokio
+//! Asynchronous interfaces for Rendezvous event streams
 //!
-//! This module contains all the Tokio support for interacting
-//! with Rendezvous event streams asynchronously.
+//! This module contains the `std::future` support for interacting
+//! with Rendezvous event streams asynchronously. Tasks are woken
+//! directly by Rendezvous, so these work with any executor.
 
-use futures::stream::Stream;
-use futures::{Async, Future, Poll};
-use mio;
-use std::sync::mpsc;
+use futures::channel::oneshot;
+use futures::stream::{FuturesUnordered, Stream, StreamExt};
+use futures::task::{AtomicWaker, Context, Poll, Waker};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::ffi::CString;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
 use tibrv_sys::*;
-use tokio::reactor::{Handle, PollEvented2};
 
-use context::{RvCtx, Transport};
-use errors::*;
-use event::{Queue, Subscription};
-use failure::*;
-use message::Msg;
+use crate::context::{RvCtx, Transport, STREAM_END_FIELD, STREAM_SEQ_FIELD};
+use crate::errors::*;
+use crate::event::{Queue, Subscription, Timer};
+use crate::field::Builder;
+use crate::message::Msg;
 
 /// Struct representing an asynchronous Rendezvous event queue.
 ///
-/// Wraps a `Queue` and sets up event callbacks in Rendezvous to
-/// drive a `Readiness` stream for use with Tokio.
+/// Wraps a `Queue` and sets up an event hook in Rendezvous to
+/// signal a `Readiness` whenever an event is placed on the queue.
 pub(crate) struct AsyncQueue {
     queue: Queue,
 }
@@ -32,16 +40,13 @@ impl AsyncQueue {
         })
     }
 
-    unsafe extern "C" fn callback(
-        _queue: tibrvQueue,
-        closure: *mut ::std::os::raw::c_void,
-    ) {
+    unsafe extern "C" fn callback(_queue: tibrvQueue, closure: *mut ::std::os::raw::c_void) {
         // As with the sync version, we can't panic and unwind into the
         // caller, so we catch any recoverable panic and ignore it.
-        let _ = ::std::panic::catch_unwind(move || {
-            let listen_ptr = closure as *mut mio::SetReadiness;
-            let _ =(&*listen_ptr).set_readiness(mio::Ready::readable());
-        });
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+            let ready = &*(closure as *const Readiness);
+            ready.wake();
+        }));
     }
 
     #[allow(dead_code)]
@@ -54,143 +59,1090 @@ impl AsyncQueue {
         }
     }
 
-    /// Asynchronously subscribe to a message subject.
-    ///
-    /// Sets up the channels as in a synchronous subscription and returns
-    /// an `AsyncSub` stream.
-    pub fn subscribe(
-        self,
-        handle: &Handle,
-        tp: &Transport,
-        subject: &str,
-    ) -> Result<AsyncSub, TibrvError> {
-        let (registration, ready) = mio::Registration::new2();
-
-        let sub = self.queue.subscribe(tp, subject)?;
-
-        // Set up event hook
-        let listener = Box::new(ready);
-        let l_ptr = &*listener as *const mio::SetReadiness;
+    // Set up the event hook, which wakes the waiting task whenever an
+    // event is placed on the queue.
+    fn register(queue: &Queue) -> Result<Arc<Readiness>, TibrvError> {
+        let ready = Arc::new(Readiness::new());
         let result = unsafe {
             tibrvQueue_SetHook(
-                sub.queue.inner,
+                queue.inner,
                 Some(AsyncQueue::callback),
-                l_ptr as *mut ::std::os::raw::c_void,
+                Arc::as_ptr(&ready) as *mut ::std::os::raw::c_void,
             )
         };
         if result != TIBRV_OK {
             Err(ErrorKind::AsyncRegError)?;
         };
+        Ok(ready)
+    }
+
+    /// Asynchronously subscribe to a message subject.
+    ///
+    /// Sets up the channels as in a synchronous subscription and returns
+    /// an `AsyncSub` stream.
+    pub fn subscribe(self, tp: &Transport, subject: &str) -> Result<AsyncSub, TibrvError> {
+        let sub = self.queue.subscribe(tp, subject)?;
+        let ready = AsyncQueue::register(&sub.queue)?;
+
+        Ok(AsyncSub { sub, ready })
+    }
+
+    // Subscribe to a new inbox, set it as the reply subject of `msg`
+    // and send it. If a `timeout` is given, a timer is created on the
+    // same queue, so both are signalled through the same readiness.
+    fn send_request(
+        self,
+        tp: &Transport,
+        msg: &mut Msg,
+        timeout: Option<Duration>,
+    ) -> Result<(AsyncSub, Option<Timer>), TibrvError> {
+        let inbox = tp.create_inbox()?;
+        let sub = self.queue.subscribe(tp, &inbox)?;
+        let timer = match timeout {
+            Some(t) => Some(self.queue.timer(t)?),
+            None => None,
+        };
+        let ready = AsyncQueue::register(&self.queue)?;
+
+        msg.set_reply_subject(&inbox)?;
+        tp.send(msg)?;
+
+        let sub = AsyncSub { sub, ready };
+        Ok((sub, timer))
+    }
+
+    /// Asynchronously send a request through `tp`.
+    ///
+    /// If a `timeout` is given, the request fails if it expires before
+    /// a reply arrives.
+    pub fn request(
+        self,
+        tp: &Transport,
+        msg: &mut Msg,
+        timeout: Option<Duration>,
+    ) -> Result<AsyncReq, TibrvError> {
+        let (sub, timer) = self.send_request(tp, msg, timeout)?;
+        Ok(AsyncReq { timer, sub })
+    }
+
+    /// Asynchronously send a request through `tp`, collecting every reply
+    /// which arrives within `window`, up to `max_replies`.
+    pub fn gather(
+        self,
+        tp: &Transport,
+        msg: &mut Msg,
+        window: Duration,
+        max_replies: usize,
+    ) -> Result<AsyncGather, TibrvError> {
+        let (sub, timer) = self.send_request(tp, msg, Some(window))?;
+        Ok(AsyncGather {
+            timer,
+            sub,
+            remaining: max_replies,
+        })
+    }
 
-        Ok(AsyncSub {
+    /// Asynchronously send a request through `tp`, which is answered with
+    /// a stream of replies.
+    pub fn request_stream(
+        self,
+        tp: &Transport,
+        msg: &mut Msg,
+        idle_timeout: Duration,
+    ) -> Result<AsyncReplyStream, TibrvError> {
+        let (sub, timer) = self.send_request(tp, msg, Some(idle_timeout))?;
+        Ok(AsyncReplyStream {
+            timer: timer.unwrap(),
             sub,
-            io: PollEvented2::new_with_handle(registration, handle)
-                .context(ErrorKind::AsyncRegError)?,
-            _listener: listener,
+            idle_timeout,
+            next_seq: 0,
+            done: false,
         })
     }
+
+    /// Create an asynchronous timer.
+    ///
+    /// Sets up the timer as in a synchronous `Timer` and returns an
+    /// `AsyncTimer` stream.
+    pub fn timer(self, interval: Duration) -> Result<AsyncTimer, TibrvError> {
+        let timer = self.queue.timer(interval)?;
+        let ready = AsyncQueue::register(&timer.queue)?;
+
+        Ok(AsyncTimer { timer, ready })
+    }
+}
+
+// Shared between a queue's hook and the task waiting on the queue.
+//
+// Only the task which most recently polled is woken. The hook marks the
+// queue as ready before waking it, so an event placed on the queue
+// between the task checking the queue and registering is not missed.
+struct Readiness {
+    ready: AtomicBool,
+    waker: AtomicWaker,
+}
+
+impl Readiness {
+    fn new() -> Self {
+        Readiness {
+            ready: AtomicBool::new(false),
+            waker: AtomicWaker::new(),
+        }
+    }
+
+    fn wake(&self) {
+        self.ready.store(true, Ordering::Release);
+        self.waker.wake();
+    }
+
+    // Register the current task to be woken by the hook, returning ready
+    // if an event has been placed on the queue since the last call.
+    fn poll_ready(&self, cx: &mut Context) -> Poll<()> {
+        self.waker.register(cx.waker());
+        if self.ready.swap(false, Ordering::AcqRel) {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
 }
 
 /// A stream returned from the `Transport::async_sub` function representing
 /// the incoming messages on the selected subject.
 pub struct AsyncSub {
+    // Declared before `ready`, so the queue is destroyed while the hook's
+    // closure is still alive.
     sub: Subscription,
-    io: PollEvented2<mio::Registration>,
-    // We need to retain ownership of the SetReadiness side of the mio registration
-    _listener: Box<mio::SetReadiness>,
+    ready: Arc<Readiness>,
 }
 
 impl AsyncSub {
     // TODO Create a more specific ErrorKind for these failures
-    fn next(&mut self) -> Result<Async<Option<Msg>>, TibrvError> {
-        // It's possible our queue was pushed into from another
-        // event, so optimistically check for a message.
-        if let Ok(msg) = self.sub.try_next() {
-            return Ok(Async::Ready(Some(msg)));
-        }
-        let ready = mio::Ready::readable();
-        if let Ok(Async::NotReady) = self.io.poll_read_ready(ready) {
-            return Ok(Async::NotReady);
-        }
+    fn try_next(&self) -> Option<Result<Msg, TibrvError>> {
         match self.sub.try_next() {
-            Err(e) => {
-                if e == mpsc::TryRecvError::Empty {
-                    self.io
-                        .clear_read_ready(ready)
-                        .expect("Failed clearing mio readiness");
-                    return Ok(Async::NotReady);
-                }
-                // Only other error from a Receiver is a broken stream
-                Err(ErrorKind::QueueError.into())
-            }
-            Ok(msg) => Ok(Async::Ready(Some(msg))),
+            Ok(msg) => Some(Ok(msg)),
+            Err(mpsc::TryRecvError::Empty) => None,
+            // Only other error from a Receiver is a broken stream
+            Err(mpsc::TryRecvError::Disconnected) => Some(Err(ErrorKind::QueueError.into())),
         }
     }
 }
 
 impl Stream for AsyncSub {
-    type Item = Msg;
-    type Error = TibrvError;
+    type Item = Result<Msg, TibrvError>;
 
-    fn poll(&mut self) -> Poll<Option<Msg>, Self::Error> {
-        Ok(self.next()?)
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(result) = self.try_next() {
+                return Poll::Ready(Some(result));
+            }
+            futures::ready!(self.ready.poll_ready(cx));
+        }
+    }
+}
+
+/// A stream of timer events, firing repeatedly at a fixed interval.
+pub struct AsyncTimer {
+    // Declared before `ready`, so the queue is destroyed while the hook's
+    // closure is still alive.
+    timer: Timer,
+    ready: Arc<Readiness>,
+}
+
+impl AsyncTimer {
+    /// Create a timer which fires repeatedly, once every `interval`.
+    pub fn new(ctx: RvCtx, interval: Duration) -> Result<Self, TibrvError> {
+        AsyncQueue::new(ctx)?.timer(interval)
+    }
+
+    /// Get the interval at which this timer fires.
+    pub fn interval(&self) -> Result<Duration, TibrvError> {
+        self.timer.interval()
+    }
+
+    /// Change the interval at which this timer fires.
+    pub fn set_interval(&mut self, interval: Duration) -> Result<(), TibrvError> {
+        self.timer.set_interval(interval)
+    }
+}
+
+impl Stream for AsyncTimer {
+    type Item = Result<(), TibrvError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        loop {
+            match self.timer.try_tick() {
+                Ok(()) => return Poll::Ready(Some(Ok(()))),
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    return Poll::Ready(Some(Err(ErrorKind::QueueError.into())))
+                }
+                Err(mpsc::TryRecvError::Empty) => {}
+            }
+            futures::ready!(self.ready.poll_ready(cx));
+        }
     }
 }
 
 /// A `Future` representing an incomplete Rendezvous request.
 ///
-/// This structure is produced by the `Transport::async_req` method.
+/// This structure is produced by the `Transport::async_req` and
+/// `Transport::async_req_timeout` methods. Dropping it destroys the
+/// listener on the reply inbox, so any late reply is discarded.
 pub struct AsyncReq {
+    // Declared before `sub`, so the timer is destroyed while the queue
+    // hook is still registered.
+    timer: Option<Timer>,
     sub: AsyncSub,
 }
 
 impl AsyncReq {
     pub fn new(sub: AsyncSub) -> Self {
-        AsyncReq { sub }
+        AsyncReq { timer: None, sub }
+    }
+
+    // Check for a reply, or for the timeout having expired.
+    fn try_complete(&self) -> Option<Result<Msg, TibrvError>> {
+        if let Some(result) = self.sub.try_next() {
+            return Some(result);
+        }
+        match self.timer {
+            Some(ref timer) if timer.try_tick().is_ok() => Some(Err(ErrorKind::Timeout.into())),
+            _ => None,
+        }
     }
 }
 
 impl Future for AsyncReq {
-    type Item = Msg;
-    type Error = TibrvError;
+    type Output = Result<Msg, TibrvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        // The reply and the timer share a queue, so are both signalled
+        // through the same readiness.
+        loop {
+            if let Some(result) = self.try_complete() {
+                return Poll::Ready(result);
+            }
+            futures::ready!(self.sub.ready.poll_ready(cx));
+        }
+    }
+}
+
+/// A `Stream` of the replies to a scatter-gather request.
+///
+/// This structure is produced by the `Transport::async_gather` method.
+/// The stream ends once the window has elapsed, or the maximum number of
+/// replies has been received.
+pub struct AsyncGather {
+    // Declared before `sub`, so the timer is destroyed while the queue
+    // hook is still registered. Dropped once the window has elapsed.
+    timer: Option<Timer>,
+    sub: AsyncSub,
+    remaining: usize,
+}
+
+impl AsyncGather {
+    fn try_next(&mut self) -> Option<Option<Result<Msg, TibrvError>>> {
+        if self.remaining == 0 || self.timer.is_none() {
+            return Some(None);
+        }
+        if let Some(result) = self.sub.try_next() {
+            self.remaining -= 1;
+            return Some(Some(result));
+        }
+        match self.timer {
+            Some(ref timer) if timer.try_tick().is_ok() => {}
+            _ => return None,
+        }
+        self.timer = None;
+        Some(None)
+    }
+}
+
+impl Stream for AsyncGather {
+    type Item = Result<Msg, TibrvError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(item) = self.try_next() {
+                return Poll::Ready(item);
+            }
+            futures::ready!(self.sub.ready.poll_ready(cx));
+        }
+    }
+}
+
+/// A `Stream` of the replies to a request answered by a `ReplyStream`.
+///
+/// This structure is produced by the `Transport::async_request_stream`
+/// method. Replies are yielded in order, and the stream ends once the
+/// end of stream marker arrives.
+///
+/// The stream fails with an error of kind `SequenceGap` if a reply has
+/// been lost, or of kind `Timeout` if no reply arrives within the idle
+/// timeout.
+pub struct AsyncReplyStream {
+    // Declared before `sub`, so the timer is destroyed while the queue
+    // hook is still registered.
+    timer: Timer,
+    sub: AsyncSub,
+    idle_timeout: Duration,
+    next_seq: u32,
+    done: bool,
+}
+
+impl AsyncReplyStream {
+    fn try_next(&mut self) -> Option<Result<Option<Msg>, TibrvError>> {
+        if self.done {
+            return Some(Ok(None));
+        }
+        if let Some(result) = self.sub.try_next() {
+            let result = result.and_then(|msg| self.accept(msg));
+            // The stream ends after the marker, or the first error.
+            self.done = result.as_ref().map(Option::is_none).unwrap_or(true);
+            return Some(result);
+        }
+        if self.timer.try_tick().is_ok() {
+            self.done = true;
+            return Some(Err(ErrorKind::Timeout.into()));
+        }
+        None
+    }
+
+    // Check the sequence of a reply, and restart the idle timeout.
+    fn accept(&mut self, msg: Msg) -> Result<Option<Msg>, TibrvError> {
+        let seq = msg
+            .get_field_by_name(STREAM_SEQ_FIELD)?
+            .try_decode::<u32>()?;
+        if seq != self.next_seq {
+            Err(ErrorKind::SequenceGap {
+                expected: self.next_seq,
+                received: seq,
+            })?
+        }
+        self.next_seq += 1;
+        if msg.get_field_by_name(STREAM_END_FIELD).is_ok() {
+            return Ok(None);
+        }
+
+        self.timer.set_interval(self.idle_timeout)?;
+        // Discard any tick which occurred before the timer was reset.
+        while self.timer.try_tick().is_ok() {}
+        Ok(Some(msg))
+    }
+}
+
+impl Stream for AsyncReplyStream {
+    type Item = Result<Msg, TibrvError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(result) = self.try_next() {
+                return Poll::Ready(result.transpose());
+            }
+            futures::ready!(self.sub.ready.poll_ready(cx));
+        }
+    }
+}
+
+// Reply subjects of an `RpcClient` start with this by default. Subjects
+// starting with an underscore are reserved by Rendezvous, so it can't use
+// `_INBOX`.
+const RPC_REPLY_PREFIX: &str = "RPC.REPLY";
+
+// How often the `Deadlines` timer fires while there are no deadlines.
+const IDLE_INTERVAL: Duration = Duration::from_secs(3600);
+
+// Deadlines for many requests, checked by a single Rendezvous timer.
+//
+// The timer is reset to fire at the earliest deadline, rather than
+// creating a timer for each request. Deadlines are not removed when a
+// request completes, so may expire for requests which are long gone.
+struct Deadlines {
+    timer: Timer,
+    heap: BinaryHeap<Reverse<(Instant, u64)>>,
+    // When the timer is next due to fire, if it's waiting for a deadline.
+    armed: Option<Instant>,
+}
+
+impl Deadlines {
+    // Create the timer on `queue`, so it's signalled through the queue's
+    // hook.
+    fn new(queue: &Queue) -> Result<Self, TibrvError> {
+        Ok(Deadlines {
+            timer: queue.timer(IDLE_INTERVAL)?,
+            heap: BinaryHeap::new(),
+            armed: None,
+        })
+    }
+
+    fn insert(&mut self, id: u64, timeout: Duration) -> Result<(), TibrvError> {
+        let deadline = Instant::now() + timeout;
+        self.heap.push(Reverse((deadline, id)));
+        if self.armed.is_none_or(|armed| deadline < armed) {
+            self.timer.set_interval(timeout)?;
+            self.armed = Some(deadline);
+        }
+        Ok(())
+    }
+
+    // Remove and return the ids whose deadlines have passed, then reset
+    // the timer for the next deadline.
+    fn expired(&mut self) -> Result<Vec<u64>, TibrvError> {
+        // Discard any ticks, each deadline is checked against the clock.
+        while self.timer.try_tick().is_ok() {}
+
+        let now = Instant::now();
+        let mut expired = Vec::new();
+        while let Some(&Reverse((deadline, id))) = self.heap.peek() {
+            if deadline > now {
+                break;
+            }
+            self.heap.pop();
+            expired.push(id);
+        }
+
+        let next = self.heap.peek().map(|&Reverse((deadline, _))| deadline);
+        if next != self.armed {
+            let interval = next.map_or(IDLE_INTERVAL, |deadline| deadline - now);
+            self.timer.set_interval(interval)?;
+            self.armed = next;
+        }
+        Ok(expired)
+    }
+}
+
+// A request awaiting its reply.
+struct Pending {
+    reply: Option<Msg>,
+    timed_out: bool,
+    waker: Option<Waker>,
+}
+
+struct ClientInner {
+    queue: Queue,
+    reply_prefix: String,
+    _sub: Subscription,
+    // Declared after the listener, but before `ready`, as the timer is
+    // signalled through the queue hook.
+    deadlines: Deadlines,
+    // Declared after the queue and listener, so the hook's closure
+    // outlives them.
+    ready: Arc<Readiness>,
+    pending: HashMap<u64, Pending>,
+    next_id: u64,
+}
+
+impl ClientInner {
+    // Dispatch every waiting event, and route replies to their requests.
+    fn drive(&mut self) -> Result<(), TibrvError> {
+        let mut dispatched = false;
+        while self.queue.poll()? {
+            dispatched = true;
+        }
+        while let Ok(msg) = self.queue.try_recv() {
+            let id = match msg.get_send_subject()? {
+                Some(subject) => subject
+                    .get(self.reply_prefix.len() + 1..)
+                    .and_then(|id| id.parse::<u64>().ok()),
+                None => None,
+            };
+            // Replies to abandoned requests are dropped here.
+            if let Some(pending) = id.and_then(|id| self.pending.get_mut(&id)) {
+                pending.reply = Some(msg);
+            }
+        }
+        for id in self.deadlines.expired()? {
+            if let Some(pending) = self.pending.get_mut(&id) {
+                pending.timed_out = true;
+            }
+        }
+        // Any request's deadline may have passed, so wake them all to check.
+        if dispatched {
+            for pending in self.pending.values_mut() {
+                if let Some(waker) = pending.waker.take() {
+                    waker.wake();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Stop tracking a request.
+    //
+    // Only the last task to poll is woken by the queue hook, so another
+    // pending request is woken to take over.
+    fn complete(&mut self, id: u64) {
+        self.pending.remove(&id);
+        let next = self.pending.values_mut().find_map(|p| p.waker.take());
+        if let Some(waker) = next {
+            waker.wake();
+        }
+    }
+}
+
+/// A client for making many concurrent asynchronous requests.
+///
+/// Unlike `Transport::async_req`, which creates an inbox, a listener and
+/// a timer for every request, an `RpcClient` listens on a single wildcard
+/// subject, and checks every timeout with one timer. Each request is sent
+/// with its own reply subject beneath that wildcard, and replies are
+/// routed back to the waiting `RpcRequest` futures.
+///
+/// # Reply subjects
+///
+/// Rendezvous can't listen for inboxes with a wildcard, so as a deliberate
+/// deviation from `Transport::request`, the reply subjects are not
+/// inboxes. They start with a prefix, `RPC.REPLY` unless set with
+/// `with_reply_prefix`, followed by the elements of an inbox name to make
+/// them unique. Replies are therefore broadcast as ordinary messages
+/// rather than sent point-to-point, so any process listening on the
+/// prefix will receive them, and they cross daemon and routing boundaries
+/// as other subjects under the prefix do.
+///
+/// ```no_run
+/// use futures::executor::block_on;
+/// use std::time::Duration;
+/// use tibrv::r#async::RpcClient;
+/// use tibrv::context::{RvCtx, TransportBuilder};
+/// use tibrv::message::Msg;
+///
+/// let ctx = RvCtx::new().unwrap();
+/// let tp = TransportBuilder::new(ctx).create().unwrap();
+/// let client = RpcClient::new(tp).unwrap();
+///
+/// let mut msg = Msg::new().unwrap();
+/// msg.set_send_subject("PRICES.REQUEST").unwrap();
+/// let reply = block_on(client.request(&mut msg, Duration::from_secs(5)).unwrap());
+/// ```
+pub struct RpcClient {
+    inner: Arc<Mutex<ClientInner>>,
+    tp: Transport,
+}
+
+impl RpcClient {
+    /// Construct a new client, sending requests through `tp`, with reply
+    /// subjects beneath `RPC.REPLY`.
+    pub fn new(tp: Transport) -> Result<Self, TibrvError> {
+        RpcClient::with_reply_prefix(tp, RPC_REPLY_PREFIX)
+    }
+
+    /// Construct a new client, sending requests through `tp`, with reply
+    /// subjects beneath `prefix`.
+    ///
+    /// Fails with `TIBRV_INVALID_SUBJECT` if `prefix` contains a wildcard.
+    pub fn with_reply_prefix(tp: Transport, prefix: &str) -> Result<Self, TibrvError> {
+        if prefix.split('.').any(|e| e == "*" || e == ">") {
+            Err(ErrorKind::from(TIBRV_INVALID_SUBJECT))?
+        }
+        let queue = Queue::new(tp.context.clone())?;
+        let inbox = tp.create_inbox()?;
+        // Drop the leading `_INBOX`, but keep the elements which make the
+        // name unique.
+        let unique = inbox.split_once('.').map_or(&inbox[..], |(_, rest)| rest);
+        let reply_prefix = format!("{}.{}", prefix, unique);
+        let sub = queue.subscribe(&tp, &format!("{}.>", reply_prefix))?;
+        let deadlines = Deadlines::new(&queue)?;
+        let ready = AsyncQueue::register(&queue)?;
+
+        Ok(RpcClient {
+            inner: Arc::new(Mutex::new(ClientInner {
+                queue,
+                reply_prefix,
+                _sub: sub,
+                deadlines,
+                ready,
+                pending: HashMap::new(),
+                next_id: 0,
+            })),
+            tp,
+        })
+    }
+
+    /// Get the transport through which requests are sent.
+    pub fn transport(&self) -> &Transport {
+        &self.tp
+    }
+
+    /// Send a request, returning a future representing the response.
+    ///
+    /// The reply subject of `msg` is overwritten. The future resolves to
+    /// an error of kind `Timeout` if no reply arrives within `timeout`.
+    pub fn request(&self, msg: &mut Msg, timeout: Duration) -> Result<RpcRequest, TibrvError> {
+        let mut inner = self.inner.lock().unwrap();
+        let id = inner.next_id;
+        msg.set_reply_subject(&format!("{}.{}", inner.reply_prefix, id))?;
+
+        self.tp.send(msg)?;
+        inner.next_id += 1;
+        inner.deadlines.insert(id, timeout)?;
+        inner.pending.insert(
+            id,
+            Pending {
+                reply: None,
+                timed_out: false,
+                waker: None,
+            },
+        );
+
+        Ok(RpcRequest {
+            id,
+            client: self.inner.clone(),
+        })
+    }
+
+    /// Get the number of requests which are still awaiting a reply.
+    pub fn in_flight(&self) -> usize {
+        self.inner.lock().unwrap().pending.len()
+    }
+}
+
+/// A `Future` representing a request made through an `RpcClient`.
+///
+/// Dropping the future abandons the request, any late reply is
+/// discarded.
+pub struct RpcRequest {
+    id: u64,
+    client: Arc<Mutex<ClientInner>>,
+}
+
+impl RpcRequest {
+    fn try_complete(&self, inner: &mut ClientInner) -> Option<Result<Msg, TibrvError>> {
+        if let Err(e) = inner.drive() {
+            return Some(Err(e));
+        }
+        let pending = inner.pending.get_mut(&self.id)?;
+        match pending.reply.take() {
+            Some(msg) => Some(Ok(msg)),
+            None if pending.timed_out => Some(Err(ErrorKind::Timeout.into())),
+            None => None,
+        }
+    }
+}
+
+impl Future for RpcRequest {
+    type Output = Result<Msg, TibrvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut inner = self.client.lock().unwrap();
+        loop {
+            if let Some(result) = self.try_complete(&mut inner) {
+                inner.complete(self.id);
+                return Poll::Ready(result);
+            }
+            if let Some(pending) = inner.pending.get_mut(&self.id) {
+                pending.waker = Some(cx.waker().clone());
+            }
+            futures::ready!(inner.ready.poll_ready(cx));
+        }
+    }
+}
+
+impl Drop for RpcRequest {
+    fn drop(&mut self) {
+        if let Ok(mut inner) = self.client.lock() {
+            inner.complete(self.id);
+        }
+    }
+}
+
+/// The name of the string field carrying the description of an error,
+/// in the error replies sent by a `Server`.
+pub const ERROR_FIELD: &str = "_error";
+
+/// Counts of the requests handled by a `Server`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ServerStats {
+    /// Requests answered by the handler.
+    pub handled: u64,
+    /// Requests for which the handler failed.
+    pub failed: u64,
+    /// Requests for which the handler missed its deadline.
+    pub timed_out: u64,
+    /// Messages discarded because they were not requests.
+    pub ignored: u64,
+    /// Replies, including error replies, which could not be sent.
+    pub send_failed: u64,
+}
+
+// Shared between a `Server` and its `ServerHandle`.
+struct ServerShared {
+    stopping: AtomicBool,
+    waker: AtomicWaker,
+    stats: Mutex<ServerStats>,
+}
+
+/// Constructs a `Server`, which answers requests on a subject.
+///
+/// ```no_run
+/// use futures::executor::block_on;
+/// use futures::future;
+/// use std::thread;
+/// use std::time::Duration;
+/// use tibrv::r#async::ServerBuilder;
+/// use tibrv::context::{RvCtx, TransportBuilder};
+///
+/// let ctx = RvCtx::new().unwrap();
+/// let tp = TransportBuilder::new(ctx).create().unwrap();
+/// let (server, handle) = ServerBuilder::new(tp, "PRICES.REQUEST")
+///     .with_max_concurrency(16)
+///     .unwrap()
+///     .with_deadline(Duration::from_secs(1))
+///     .create(|request| future::ready(request.try_clone()))
+///     .unwrap();
+///
+/// let server = thread::spawn(move || block_on(server));
+/// // ...
+/// let stats = block_on(handle.shutdown());
+/// ```
+pub struct ServerBuilder {
+    tp: Transport,
+    subject: String,
+    max_concurrency: usize,
+    deadline: Option<Duration>,
+}
 
-    fn poll(&mut self) -> Result<Async<Self::Item>, Self::Error> {
-        match self.sub.poll().unwrap() {
-            Async::Ready(Some(v)) => Ok(Async::Ready(v)),
-            Async::Ready(None) => Err(ErrorKind::QueueError.into()),
-            Async::NotReady => Ok(Async::NotReady),
+impl ServerBuilder {
+    /// Constructs a new ServerBuilder, answering requests on `subject`
+    /// through `tp`.
+    ///
+    /// By default there is no limit on the number of requests handled
+    /// concurrently, and no deadline.
+    pub fn new(tp: Transport, subject: &str) -> Self {
+        ServerBuilder {
+            tp,
+            subject: subject.to_owned(),
+            max_concurrency: usize::MAX,
+            deadline: None,
+        }
+    }
+
+    /// Sets the maximum number of requests handled concurrently.
+    ///
+    /// Further requests wait on the queue until a handler completes.
+    /// Fails if `max_concurrency` is zero.
+    pub fn with_max_concurrency(mut self, max_concurrency: usize) -> Result<Self, TibrvError> {
+        if max_concurrency == 0 {
+            Err(ErrorKind::from(TIBRV_INVALID_ARG))?
+        }
+        self.max_concurrency = max_concurrency;
+        Ok(self)
+    }
+
+    /// Sets a deadline for handling each request.
+    ///
+    /// If the handler has not completed within `deadline`, it is dropped
+    /// and an error reply is sent instead.
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Consumes the `ServerBuilder`, subscribing to the subject.
+    ///
+    /// Each request is passed to `handler`, and the message it resolves
+    /// to is sent as the reply with `send_reply`. If the handler fails, an
+    /// error reply is sent instead, containing only a description of the
+    /// error in the `ERROR_FIELD` string field. Messages which are not
+    /// requests are discarded, and replies which can't be sent are counted
+    /// in `ServerStats::send_failed`.
+    ///
+    /// Returns the `Server` future, which must be polled to serve
+    /// requests, and a `ServerHandle` to shut it down.
+    pub fn create<F, G>(self, handler: F) -> Result<(Server<F, G>, ServerHandle), TibrvError>
+    where
+        F: FnMut(&Msg) -> G,
+        G: Future<Output = Result<Msg, TibrvError>>,
+    {
+        let sub = self.tp.async_sub(&self.subject)?;
+        let deadlines = match self.deadline {
+            Some(deadline) => Some(ServerDeadlines::new(self.tp.context.clone(), deadline)?),
+            None => None,
+        };
+        let shared = Arc::new(ServerShared {
+            stopping: AtomicBool::new(false),
+            waker: AtomicWaker::new(),
+            stats: Mutex::new(ServerStats::default()),
+        });
+        let (done, finished) = oneshot::channel();
+
+        let server = Server {
+            sub: Some(sub),
+            tp: self.tp,
+            handler,
+            in_flight: FuturesUnordered::new(),
+            max_concurrency: self.max_concurrency,
+            deadlines,
+            next_id: 0,
+            shared: shared.clone(),
+            done: Some(done),
+        };
+        let handle = ServerHandle { shared, finished };
+        Ok((server, handle))
+    }
+}
+
+/// A `Future` which answers requests, created by a `ServerBuilder`.
+///
+/// The server runs until it is shut down through its `ServerHandle`,
+/// then resolves to the final `ServerStats`. It fails if the
+/// subscription breaks, a reply which can't be sent only fails that
+/// request.
+pub struct Server<F, G> {
+    // Dropped on shutdown, to stop accepting requests.
+    sub: Option<AsyncSub>,
+    tp: Transport,
+    handler: F,
+    in_flight: FuturesUnordered<Handling<G>>,
+    max_concurrency: usize,
+    deadlines: Option<ServerDeadlines>,
+    next_id: u64,
+    shared: Arc<ServerShared>,
+    done: Option<oneshot::Sender<ServerStats>>,
+}
+
+// No field is structurally pinned, the handler futures are boxed.
+impl<F, G> Unpin for Server<F, G> {}
+
+impl<F, G> Server<F, G>
+where
+    F: FnMut(&Msg) -> G,
+    G: Future<Output = Result<Msg, TibrvError>>,
+{
+    // Start handling each waiting request, up to the concurrency limit.
+    fn accept(&mut self, cx: &mut Context) -> Result<bool, TibrvError> {
+        let mut accepted = false;
+        while self.in_flight.len() < self.max_concurrency {
+            let msg = match self.sub {
+                Some(ref mut sub) => match Pin::new(sub).poll_next(cx) {
+                    Poll::Ready(Some(msg)) => msg?,
+                    Poll::Ready(None) | Poll::Pending => break,
+                },
+                None => break,
+            };
+            accepted = true;
+            if msg.get_reply_subject()?.is_none() {
+                self.shared.stats.lock().unwrap().ignored += 1;
+                continue;
+            }
+            let id = self.next_id;
+            self.next_id += 1;
+            if let Some(ref mut deadlines) = self.deadlines {
+                deadlines.deadlines.insert(id, deadlines.deadline)?;
+            }
+            let reply = Box::pin((self.handler)(&msg));
+            self.in_flight.push(Handling {
+                id,
+                request: Some(msg),
+                reply,
+                expired: false,
+                waker: None,
+            });
+        }
+        Ok(accepted)
+    }
+
+    // Mark each request whose deadline has passed, and wake its
+    // `Handling` to send the error reply.
+    fn expire(&mut self, cx: &mut Context) -> Result<(), TibrvError> {
+        let deadlines = match self.deadlines {
+            Some(ref mut deadlines) => deadlines,
+            None => return Ok(()),
+        };
+        // Register to be woken by the timer before checking, so a
+        // deadline passing in between isn't missed.
+        let _ = deadlines.ready.poll_ready(cx);
+        let expired = deadlines.deadlines.expired()?;
+        if expired.is_empty() {
+            return Ok(());
+        }
+        for handling in self.in_flight.iter_mut() {
+            if expired.contains(&handling.id) {
+                handling.expired = true;
+                if let Some(waker) = handling.waker.take() {
+                    waker.wake();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Send the reply to each request whose handler has completed.
+    fn reply(&mut self, cx: &mut Context) -> Result<bool, TibrvError> {
+        let mut replied = false;
+        while let Poll::Ready(Some((request, result))) = self.in_flight.poll_next_unpin(cx) {
+            replied = true;
+            let mut reply = match result {
+                Some(Ok(reply)) => {
+                    self.shared.stats.lock().unwrap().handled += 1;
+                    reply
+                }
+                Some(Err(e)) => {
+                    self.shared.stats.lock().unwrap().failed += 1;
+                    error_reply(&e)?
+                }
+                None => {
+                    self.shared.stats.lock().unwrap().timed_out += 1;
+                    error_reply(&ErrorKind::Timeout.into())?
+                }
+            };
+            // The requester may be gone, which shouldn't stop the server.
+            if self.tp.send_reply(&mut reply, &request).is_err() {
+                self.shared.stats.lock().unwrap().send_failed += 1;
+            }
+        }
+        Ok(replied)
+    }
+}
+
+impl<F, G> Future for Server<F, G>
+where
+    F: FnMut(&Msg) -> G,
+    G: Future<Output = Result<Msg, TibrvError>>,
+{
+    type Output = Result<ServerStats, TibrvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        this.shared.waker.register(cx.waker());
+        if this.shared.stopping.load(Ordering::Acquire) {
+            // Destroys the listener, any waiting requests are discarded.
+            this.sub = None;
+        }
+
+        this.expire(cx)?;
+        // Completing a handler makes room to accept another request.
+        loop {
+            let accepted = this.accept(cx)?;
+            let replied = this.reply(cx)?;
+            if !accepted && !replied {
+                break;
+            }
+        }
+
+        if this.sub.is_none() && this.in_flight.is_empty() {
+            let stats = *this.shared.stats.lock().unwrap();
+            if let Some(done) = this.done.take() {
+                let _ = done.send(stats);
+            }
+            return Poll::Ready(Ok(stats));
         }
+        Poll::Pending
+    }
+}
+
+// Build a reply describing a handler's failure.
+fn error_reply(error: &TibrvError) -> Result<Msg, TibrvError> {
+    let description = CString::new(error.to_string()).unwrap_or_default();
+    let mut reply = Msg::new()?;
+    let mut field = Builder::new(&description.as_c_str())
+        .with_name(ERROR_FIELD)
+        .encode();
+    reply.add_field(&mut field)?;
+    Ok(reply)
+}
+
+// The deadlines of the requests a `Server` is handling, checked by one
+// timer on its own queue.
+struct ServerDeadlines {
+    deadline: Duration,
+    // Declared before `ready`, so the queue is destroyed while the hook's
+    // closure is still alive.
+    deadlines: Deadlines,
+    ready: Arc<Readiness>,
+}
+
+impl ServerDeadlines {
+    fn new(ctx: RvCtx, deadline: Duration) -> Result<Self, TibrvError> {
+        let queue = Queue::new(ctx)?;
+        let deadlines = Deadlines::new(&queue)?;
+        let ready = AsyncQueue::register(&queue)?;
+        Ok(ServerDeadlines {
+            deadline,
+            deadlines,
+            ready,
+        })
     }
 }
 
-pub(crate) struct AsyncReply<F> {
-    pub subject: String,
-    pub future: F,
+// A request being handled by a `Server`.
+struct Handling<G> {
+    id: u64,
+    request: Option<Msg>,
+    reply: Pin<Box<G>>,
+    // Set by the server once the deadline has passed.
+    expired: bool,
+    waker: Option<Waker>,
 }
 
-impl<F> Future for AsyncReply<F>
+impl<G> Future for Handling<G>
 where
-    F: Future<Item = Msg, Error = TibrvError>,
+    G: Future<Output = Result<Msg, TibrvError>>,
 {
-    type Item = F::Item;
-    type Error = F::Error;
+    // The request, with the result of the handler, or `None` if the
+    // deadline passed first.
+    type Output = (Msg, Option<Result<Msg, TibrvError>>);
 
-    fn poll(&mut self) -> Result<Async<Self::Item>, Self::Error> {
-        match self.future.poll()? {
-            Async::Ready(mut msg) => {
-                msg.set_send_subject(&self.subject).unwrap();
-                Ok(Async::Ready(msg))
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let result = match self.reply.as_mut().poll(cx) {
+            Poll::Ready(result) => Some(result),
+            Poll::Pending if self.expired => None,
+            Poll::Pending => {
+                self.waker = Some(cx.waker().clone());
+                return Poll::Pending;
             }
-            Async::NotReady => Ok(Async::NotReady),
+        };
+        let request = self
+            .request
+            .take()
+            .expect("Handling polled after completion");
+        Poll::Ready((request, result))
+    }
+}
+
+/// A handle for shutting down a `Server`.
+pub struct ServerHandle {
+    shared: Arc<ServerShared>,
+    finished: oneshot::Receiver<ServerStats>,
+}
+
+impl ServerHandle {
+    /// Get the counts of the requests handled so far.
+    pub fn stats(&self) -> ServerStats {
+        *self.shared.stats.lock().unwrap()
+    }
+
+    /// Shut down the server.
+    ///
+    /// The server stops accepting requests, and any which are waiting
+    /// are discarded. The returned future resolves to the final stats
+    /// once every request in flight has been answered.
+    pub async fn shutdown(self) -> ServerStats {
+        self.shared.stopping.store(true, Ordering::Release);
+        self.shared.waker.wake();
+        match self.finished.await {
+            Ok(stats) => stats,
+            // The server failed, or was dropped.
+            Err(_) => *self.shared.stats.lock().unwrap(),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use async::AsyncQueue;
-    use context::{RvCtx, TransportBuilder};
-    use tokio::reactor::Handle;
+    use crate::context::{RvCtx, Transport, TransportBuilder, STREAM_SEQ_FIELD};
+    use crate::errors::{ErrorKind, TibrvError};
+    use crate::field::Builder;
+    use crate::message::Msg;
+    use crate::r#async::{
+        AsyncQueue, AsyncTimer, RpcClient, ServerBuilder, ServerStats, ERROR_FIELD,
+    };
+    use crate::test_util::{reply, spawn_server, spawn_server_with};
+    use futures::executor::block_on;
+    use futures::{future, StreamExt, TryStreamExt};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
 
     #[test]
     fn no_hook() {
@@ -200,15 +1152,521 @@ mod tests {
     }
 
     #[test]
-    #[ignore]
     fn has_hook() {
-        let handle = Handle::default();
-
         let ctx = RvCtx::new().unwrap();
-        let tp = TransportBuilder::new(ctx.clone()).create().unwrap();
+        let tp = Transport::intra_process(&ctx);
         let queue = AsyncQueue::new(ctx.clone()).unwrap();
 
         assert_eq!(false, queue.has_hook());
-        let _ = queue.subscribe(&handle, &tp, "TEST").unwrap();
+        let _ = queue.subscribe(&tp, "TEST").unwrap();
+    }
+
+    #[test]
+    fn timer_stream() {
+        let ctx = RvCtx::new().unwrap();
+        let timer = AsyncTimer::new(ctx, Duration::from_millis(10)).unwrap();
+
+        let ticks = block_on(timer.take(3).try_collect::<Vec<_>>()).unwrap();
+        assert_eq!(3, ticks.len());
+    }
+
+    #[test]
+    fn sub_stream() {
+        let ctx = RvCtx::new().unwrap();
+        let tp = Transport::intra_process(&ctx);
+        let sub = tp.async_sub("TEST.ASYNC.SUB").unwrap();
+
+        // Send from another thread, so the task is woken by the queue hook.
+        let sender = thread::spawn(move || {
+            for _ in 0..3 {
+                thread::sleep(Duration::from_millis(10));
+                let mut msg = Msg::new().unwrap();
+                msg.set_send_subject("TEST.ASYNC.SUB").unwrap();
+                tp.send(&mut msg).unwrap();
+            }
+        });
+        assert_eq!(
+            3,
+            block_on(sub.take(3).try_collect::<Vec<_>>()).unwrap().len()
+        );
+        sender.join().unwrap();
+    }
+
+    #[test]
+    fn request_timeout() {
+        let ctx = RvCtx::new().unwrap();
+        let tp = Transport::intra_process(&ctx);
+
+        let mut msg = Msg::new().unwrap();
+        msg.set_send_subject("TEST.ASYNC.TIMEOUT").unwrap();
+        let req = tp
+            .async_req_timeout(&mut msg, Duration::from_millis(50))
+            .unwrap();
+
+        match block_on(req) {
+            Err(e) => assert_eq!(ErrorKind::Timeout, e.kind()),
+            Ok(_) => panic!("Expected a timeout"),
+        }
+    }
+
+    #[test]
+    fn request_reply_before_timeout() {
+        let ctx = RvCtx::new().unwrap();
+        let tp = Transport::intra_process(&ctx);
+        let server = spawn_server(&ctx, "TEST.ASYNC.REQUEST", reply);
+
+        let mut msg = Msg::new().unwrap();
+        msg.set_send_subject("TEST.ASYNC.REQUEST").unwrap();
+        let req = tp
+            .async_req_timeout(&mut msg, Duration::from_secs(5))
+            .unwrap();
+
+        assert!(block_on(req).is_ok());
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn serve() {
+        let ctx = RvCtx::new().unwrap();
+        let tp = Transport::intra_process(&ctx);
+        let server = Transport::intra_process(&ctx).async_serve("TEST.ASYNC.SERVE", |msg| {
+            let fail = msg.get_field_by_name("fail").is_ok();
+            future::ready(if fail {
+                Err(ErrorKind::FieldTypeError.into())
+            } else {
+                msg.try_clone()
+            })
+        });
+
+        let server = thread::spawn(move || block_on(server));
+        let mut msg = Msg::new().unwrap();
+        msg.set_send_subject("TEST.ASYNC.SERVE").unwrap();
+        // The server may not have subscribed yet, so retry the request.
+        let reply = (0..50).find_map(|_| tp.request(&mut msg, Some(0.1)).ok());
+        assert!(reply.is_some());
+
+        // A message which isn't a request is skipped.
+        let mut msg = Msg::new().unwrap();
+        msg.set_send_subject("TEST.ASYNC.SERVE").unwrap();
+        tp.send(&mut msg).unwrap();
+        let mut msg = Msg::new().unwrap();
+        msg.set_send_subject("TEST.ASYNC.SERVE").unwrap();
+        assert!(tp.request(&mut msg, Some(5.0)).is_ok());
+
+        // A failing handler stops the server.
+        let mut msg = Msg::new().unwrap();
+        msg.set_send_subject("TEST.ASYNC.SERVE").unwrap();
+        let mut field = Builder::new(&true).with_name("fail").encode();
+        msg.add_field(&mut field).unwrap();
+        tp.send(&mut msg).unwrap();
+        match server.join().unwrap() {
+            Err(e) => assert_eq!(ErrorKind::FieldTypeError, e.kind()),
+            Ok(_) => panic!("Expected an error"),
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn spawn_serve() {
+        let ctx = RvCtx::new().unwrap();
+        let tp = Transport::intra_process(&ctx);
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let _guard = rt.enter();
+        let server = Transport::intra_process(&ctx)
+            .spawn_serve("TEST.ASYNC.SPAWN", |msg| future::ready(msg.try_clone()));
+        let mut msg = Msg::new().unwrap();
+        msg.set_send_subject("TEST.ASYNC.SPAWN").unwrap();
+        // The server may not have subscribed yet, so retry the request.
+        let reply = (0..50).find_map(|_| tp.request(&mut msg, Some(0.1)).ok());
+        assert!(reply.is_some());
+        server.abort();
+    }
+
+    // Serve `count` requests on `subject`, replying with the request's
+    // own reply subject.
+    fn echo_server(ctx: &RvCtx, subject: &str, count: usize) -> thread::JoinHandle<()> {
+        spawn_server(ctx, subject, move |tp, sub| {
+            for _ in 0..count {
+                let request = sub.next().unwrap();
+                let mut reply = Msg::new().unwrap();
+                let reply_subject = request.get_reply_subject().unwrap().unwrap();
+                reply.set_reply_subject(&reply_subject).unwrap();
+                tp.send_reply(&mut reply, &request).unwrap();
+            }
+        })
+    }
+
+    #[test]
+    fn rpc_client() {
+        let ctx = RvCtx::new().unwrap();
+        let server = echo_server(&ctx, "TEST.RPC", 3);
+
+        let client = RpcClient::new(Transport::intra_process(&ctx)).unwrap();
+        let requests = (0..3)
+            .map(|_| {
+                let mut msg = Msg::new().unwrap();
+                msg.set_send_subject("TEST.RPC").unwrap();
+                let req = client.request(&mut msg, Duration::from_secs(5)).unwrap();
+                let expected = msg.get_reply_subject().unwrap();
+                // Each reply must be routed to the request it answers.
+                async move {
+                    let reply = req.await?;
+                    assert_eq!(expected, reply.get_reply_subject()?);
+                    Ok::<_, TibrvError>(())
+                }
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(3, client.in_flight());
+
+        block_on(future::try_join_all(requests)).unwrap();
+        assert_eq!(0, client.in_flight());
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn rpc_client_reply_prefix() {
+        let ctx = RvCtx::new().unwrap();
+        let tp = Transport::intra_process(&ctx);
+        assert!(RpcClient::with_reply_prefix(tp, "TEST.*").is_err());
+
+        let server = echo_server(&ctx, "TEST.RPC.PREFIX", 1);
+        let client =
+            RpcClient::with_reply_prefix(Transport::intra_process(&ctx), "TEST.REPLIES").unwrap();
+        let mut msg = Msg::new().unwrap();
+        msg.set_send_subject("TEST.RPC.PREFIX").unwrap();
+        let req = client.request(&mut msg, Duration::from_secs(5)).unwrap();
+        let reply_subject = msg.get_reply_subject().unwrap().unwrap();
+        assert!(reply_subject.starts_with("TEST.REPLIES."));
+        assert!(block_on(req).is_ok());
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn rpc_client_timeout() {
+        let ctx = RvCtx::new().unwrap();
+        let client = RpcClient::new(Transport::intra_process(&ctx)).unwrap();
+
+        let mut msg = Msg::new().unwrap();
+        msg.set_send_subject("TEST.RPC.TIMEOUT").unwrap();
+        let req = client.request(&mut msg, Duration::from_millis(50)).unwrap();
+
+        match block_on(req) {
+            Err(e) => assert_eq!(ErrorKind::Timeout, e.kind()),
+            Ok(_) => panic!("Expected a timeout"),
+        }
+        assert_eq!(0, client.in_flight());
+    }
+
+    #[test]
+    fn rpc_client_timeouts() {
+        let ctx = RvCtx::new().unwrap();
+        let client = RpcClient::new(Transport::intra_process(&ctx)).unwrap();
+
+        let mut msg = Msg::new().unwrap();
+        msg.set_send_subject("TEST.RPC.TIMEOUTS").unwrap();
+        let long = client
+            .request(&mut msg, Duration::from_millis(200))
+            .unwrap();
+        let short = client.request(&mut msg, Duration::from_millis(50)).unwrap();
+
+        // The shared timer is reset for each earlier deadline.
+        match block_on(short) {
+            Err(e) => assert_eq!(ErrorKind::Timeout, e.kind()),
+            Ok(_) => panic!("Expected a timeout"),
+        }
+        assert_eq!(1, client.in_flight());
+        match block_on(long) {
+            Err(e) => assert_eq!(ErrorKind::Timeout, e.kind()),
+            Ok(_) => panic!("Expected a timeout"),
+        }
+        assert_eq!(0, client.in_flight());
+    }
+
+    // Rendezvous doesn't deliver inbox messages to wildcard listeners, as
+    // in `MockTransport`, but only a network transport shows this.
+    #[test]
+    #[ignore] // Needs a Rendezvous daemon
+    fn rpc_client_network() {
+        let ctx = RvCtx::new().unwrap();
+        let network = |ctx: &RvCtx| TransportBuilder::new(ctx.clone()).create().unwrap();
+        let server = spawn_server_with(&ctx, "TEST.RPC.NETWORK", network, reply);
+
+        let client = RpcClient::new(TransportBuilder::new(ctx).create().unwrap()).unwrap();
+        let mut msg = Msg::new().unwrap();
+        msg.set_send_subject("TEST.RPC.NETWORK").unwrap();
+        let req = client.request(&mut msg, Duration::from_secs(5)).unwrap();
+        assert!(block_on(req).is_ok());
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn rpc_client_abandon() {
+        let ctx = RvCtx::new().unwrap();
+        let client = RpcClient::new(Transport::intra_process(&ctx)).unwrap();
+
+        let mut msg = Msg::new().unwrap();
+        msg.set_send_subject("TEST.RPC.ABANDON").unwrap();
+        let req = client.request(&mut msg, Duration::from_secs(5)).unwrap();
+        assert_eq!(1, client.in_flight());
+        drop(req);
+        assert_eq!(0, client.in_flight());
+    }
+
+    #[test]
+    fn gather_stream() {
+        let ctx = RvCtx::new().unwrap();
+        let servers = vec![
+            echo_server(&ctx, "TEST.ASYNC.GATHER", 1),
+            echo_server(&ctx, "TEST.ASYNC.GATHER", 1),
+        ];
+
+        let tp = Transport::intra_process(&ctx);
+        let mut msg = Msg::new().unwrap();
+        msg.set_send_subject("TEST.ASYNC.GATHER").unwrap();
+        let replies = tp
+            .async_gather(&mut msg, Duration::from_millis(200), 10)
+            .unwrap();
+
+        assert_eq!(2, block_on(replies.try_collect::<Vec<_>>()).unwrap().len());
+        for server in servers {
+            server.join().unwrap();
+        }
+    }
+
+    // Answer one request on `subject` by sending replies with the given
+    // sequence numbers, without an end of stream marker.
+    fn stream_server(ctx: &RvCtx, subject: &str, seqs: Vec<u32>) -> thread::JoinHandle<()> {
+        spawn_server(ctx, subject, move |tp, sub| {
+            let request = sub.next().unwrap();
+            for seq in seqs {
+                let mut chunk = Msg::new().unwrap();
+                let mut field = Builder::new(&seq).with_name(STREAM_SEQ_FIELD).encode();
+                chunk.add_field(&mut field).unwrap();
+                tp.send_reply(&mut chunk, &request).unwrap();
+            }
+        })
+    }
+
+    #[test]
+    fn request_stream() {
+        let ctx = RvCtx::new().unwrap();
+        let server = spawn_server(&ctx, "TEST.ASYNC.STREAM", |tp, sub| {
+            let request = sub.next().unwrap();
+            let mut replies = tp.reply_stream(&request).unwrap();
+            for _ in 0..5 {
+                replies.send(&mut Msg::new().unwrap()).unwrap();
+            }
+            replies.finish().unwrap();
+        });
+
+        let tp = Transport::intra_process(&ctx);
+        let mut msg = Msg::new().unwrap();
+        msg.set_send_subject("TEST.ASYNC.STREAM").unwrap();
+        let chunks = tp
+            .async_request_stream(&mut msg, Duration::from_secs(5))
+            .unwrap();
+
+        assert_eq!(5, block_on(chunks.try_collect::<Vec<_>>()).unwrap().len());
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn request_stream_gap() {
+        let ctx = RvCtx::new().unwrap();
+        let server = stream_server(&ctx, "TEST.ASYNC.STREAM.GAP", vec![0, 2]);
+
+        let tp = Transport::intra_process(&ctx);
+        let mut msg = Msg::new().unwrap();
+        msg.set_send_subject("TEST.ASYNC.STREAM.GAP").unwrap();
+        let chunks = tp
+            .async_request_stream(&mut msg, Duration::from_secs(5))
+            .unwrap();
+
+        match block_on(chunks.try_collect::<Vec<_>>()) {
+            Err(e) => assert_eq!(
+                ErrorKind::SequenceGap {
+                    expected: 1,
+                    received: 2
+                },
+                e.kind()
+            ),
+            Ok(_) => panic!("Expected a sequence gap"),
+        }
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn request_stream_idle_timeout() {
+        let ctx = RvCtx::new().unwrap();
+        // The end of stream marker is never sent.
+        let server = stream_server(&ctx, "TEST.ASYNC.STREAM.IDLE", vec![0, 1]);
+
+        let tp = Transport::intra_process(&ctx);
+        let mut msg = Msg::new().unwrap();
+        msg.set_send_subject("TEST.ASYNC.STREAM.IDLE").unwrap();
+        let mut chunks = tp
+            .async_request_stream(&mut msg, Duration::from_millis(100))
+            .unwrap();
+
+        block_on(async {
+            assert!(chunks.next().await.unwrap().is_ok());
+            assert!(chunks.next().await.unwrap().is_ok());
+            match chunks.next().await.unwrap() {
+                Err(e) => assert_eq!(ErrorKind::Timeout, e.kind()),
+                Ok(_) => panic!("Expected a timeout"),
+            }
+            assert!(chunks.next().await.is_none());
+        });
+        server.join().unwrap();
+    }
+
+    // Send a request on `subject`, marking it to fail if `fail` is set.
+    fn request(tp: &Transport, subject: &str, fail: bool) -> Msg {
+        let mut msg = Msg::new().unwrap();
+        msg.set_send_subject(subject).unwrap();
+        if fail {
+            let mut field = Builder::new(&true).with_name("fail").encode();
+            msg.add_field(&mut field).unwrap();
+        }
+        tp.request(&mut msg, Some(5.0)).unwrap()
+    }
+
+    #[test]
+    fn server_error_replies() {
+        let ctx = RvCtx::new().unwrap();
+        let tp = Transport::intra_process(&ctx);
+        let (server, handle) = ServerBuilder::new(Transport::intra_process(&ctx), "TEST.SERVER")
+            .create(|msg: &Msg| {
+                future::ready(match msg.get_field_by_name("fail") {
+                    Ok(_) => Err(ErrorKind::QueueError.into()),
+                    Err(_) => msg.try_clone(),
+                })
+            })
+            .unwrap();
+        let server = thread::spawn(move || block_on(server));
+
+        assert!(request(&tp, "TEST.SERVER", false)
+            .get_field_by_name(ERROR_FIELD)
+            .is_err());
+        // A failure doesn't stop the server.
+        assert!(request(&tp, "TEST.SERVER", true)
+            .get_field_by_name(ERROR_FIELD)
+            .is_ok());
+        // Nor does a message which isn't a request, it's handled before
+        // the following request.
+        let mut msg = Msg::new().unwrap();
+        msg.set_send_subject("TEST.SERVER").unwrap();
+        tp.send(&mut msg).unwrap();
+        assert!(request(&tp, "TEST.SERVER", false)
+            .get_field_by_name(ERROR_FIELD)
+            .is_err());
+        // Nor does a reply which can't be sent, Rendezvous won't send to
+        // a wildcard subject.
+        let mut msg = Msg::new().unwrap();
+        msg.set_send_subject("TEST.SERVER").unwrap();
+        msg.set_reply_subject("TEST.SERVER.>").unwrap();
+        tp.send(&mut msg).unwrap();
+        assert!(request(&tp, "TEST.SERVER", false)
+            .get_field_by_name(ERROR_FIELD)
+            .is_err());
+
+        let stats = block_on(handle.shutdown());
+        let expected = ServerStats {
+            handled: 4,
+            failed: 1,
+            timed_out: 0,
+            ignored: 1,
+            send_failed: 1,
+        };
+        assert_eq!(expected, stats);
+        assert_eq!(expected, server.join().unwrap().unwrap());
+    }
+
+    #[test]
+    fn server_deadline() {
+        let ctx = RvCtx::new().unwrap();
+        let tp = Transport::intra_process(&ctx);
+        let (server, handle) =
+            ServerBuilder::new(Transport::intra_process(&ctx), "TEST.SERVER.DEADLINE")
+                .with_deadline(Duration::from_millis(50))
+                .create(|_| future::pending())
+                .unwrap();
+        let server = thread::spawn(move || block_on(server));
+
+        // Both deadlines are checked by the same timer.
+        for _ in 0..2 {
+            let reply = request(&tp, "TEST.SERVER.DEADLINE", false);
+            assert!(reply.get_field_by_name(ERROR_FIELD).is_ok());
+        }
+        assert_eq!(2, block_on(handle.shutdown()).timed_out);
+        server.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn server_handler_timeout() {
+        let ctx = RvCtx::new().unwrap();
+        let tp = Transport::intra_process(&ctx);
+        let (server, handle) =
+            ServerBuilder::new(Transport::intra_process(&ctx), "TEST.SERVER.TIMEOUT")
+                .with_deadline(Duration::from_secs(5))
+                .create(|_| future::ready(Err(ErrorKind::Timeout.into())))
+                .unwrap();
+        let server = thread::spawn(move || block_on(server));
+
+        // A handler failing with a timeout of its own hasn't missed the
+        // deadline.
+        let reply = request(&tp, "TEST.SERVER.TIMEOUT", false);
+        assert!(reply.get_field_by_name(ERROR_FIELD).is_ok());
+        let stats = block_on(handle.shutdown());
+        assert_eq!((1, 0), (stats.failed, stats.timed_out));
+        server.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn server_max_concurrency() {
+        let ctx = RvCtx::new().unwrap();
+        assert!(ServerBuilder::new(Transport::intra_process(&ctx), "TEST")
+            .with_max_concurrency(0)
+            .is_err());
+
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let (handler_running, handler_peak, handler_ctx) =
+            (running.clone(), peak.clone(), ctx.clone());
+        let (server, handle) =
+            ServerBuilder::new(Transport::intra_process(&ctx), "TEST.SERVER.LIMIT")
+                .with_max_concurrency(2)
+                .unwrap()
+                .create(move |msg: &Msg| {
+                    let reply = msg.try_clone();
+                    let now = handler_running.fetch_add(1, Ordering::SeqCst) + 1;
+                    handler_peak.fetch_max(now, Ordering::SeqCst);
+                    let running = handler_running.clone();
+                    let delay = AsyncTimer::new(handler_ctx.clone(), Duration::from_millis(20));
+                    async move {
+                        delay?.next().await;
+                        running.fetch_sub(1, Ordering::SeqCst);
+                        reply
+                    }
+                })
+                .unwrap();
+        let server = thread::spawn(move || block_on(server));
+
+        let clients = (0..6)
+            .map(|_| {
+                let tp = Transport::intra_process(&ctx);
+                thread::spawn(move || {
+                    request(&tp, "TEST.SERVER.LIMIT", false);
+                })
+            })
+            .collect::<Vec<_>>();
+        for client in clients {
+            client.join().unwrap();
+        }
+
+        assert_eq!(6, block_on(handle.shutdown()).handled);
+        assert!(peak.load(Ordering::SeqCst) <= 2);
+        server.join().unwrap().unwrap();
     }
 }
@@ -1,22 +1,100 @@
 //! Interface for creating and managing the Rendezvous internal machinery
 
-use errors::*;
-use event::{Queue, Subscription};
+use crate::errors::*;
+use crate::event::{Queue, RvSubscription, Subscription};
+use crate::field::Builder;
+use crate::message::Msg;
 use failure::*;
-use message::Msg;
 use std::ffi::{CStr, CString};
 use std::mem;
 use std::ptr::null;
+#[cfg(feature = "tibrv_8_4")]
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+#[cfg(feature = "async")]
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use tibrv_sys::*;
 
+#[cfg(feature = "async")]
+use crate::r#async::{AsyncGather, AsyncQueue, AsyncReplyStream, AsyncReq, AsyncSub};
+#[cfg(feature = "async")]
+use futures::sink::Sink;
+#[cfg(feature = "async")]
+use futures::stream::StreamExt;
+#[cfg(feature = "async")]
+use futures::task::{Context, Poll};
+#[cfg(feature = "async")]
+use std::future::Future;
+#[cfg(feature = "async")]
+use std::pin::Pin;
 #[cfg(feature = "tokio")]
-use async::{AsyncQueue, AsyncReply, AsyncReq, AsyncSub};
-#[cfg(feature = "tokio")]
-use futures::prelude::{
-    Async, AsyncSink, Future, IntoFuture, Poll, Sink, StartSend, Stream,
-};
-#[cfg(feature = "tokio")]
-use tokio::reactor::Handle;
+use tokio::task::JoinHandle;
+
+/// An interface over anything able to carry Rendezvous messages.
+///
+/// [`Transport`] implements this trait using the Rendezvous library, and
+/// [`MockTransport`] provides an in-memory implementation, so code written
+/// against `RvTransport` can be tested without libtibrv network access or
+/// a running daemon.
+///
+/// [`Transport`]: struct.Transport.html
+/// [`MockTransport`]: ../mock/struct.MockTransport.html
+pub trait RvTransport {
+    /// The type of subscription returned by `subscribe`.
+    type Subscription: RvSubscription;
+
+    /// Send a `Msg` through this transport.
+    fn send(&self, msg: &mut Msg) -> Result<(), TibrvError>;
+
+    /// Subscribe to a message subject, wildcards are accepted.
+    fn subscribe(&self, subject: &str) -> Result<Self::Subscription, TibrvError>;
+
+    /// Creates an unique inbox subject name.
+    fn create_inbox(&self) -> Result<String, TibrvError>;
+
+    /// Send a request, blocking until a response is received or `timeout`
+    /// seconds have elapsed.
+    ///
+    /// A `None` parameter for `timeout` means block indefinitely. If no
+    /// response arrives in time, an error of kind `Timeout` is returned.
+    fn request(&self, msg: &mut Msg, timeout: Option<f64>) -> Result<Msg, TibrvError>;
+
+    /// Send `reply` in response to `request`.
+    ///
+    /// Fails with `ErrorKind::NotARequest` if `request` has no reply
+    /// subject. The default implementation sets the send subject of
+    /// `reply` to the reply subject of `request`, then sends it.
+    fn send_reply(&self, reply: &mut Msg, request: &Msg) -> Result<(), TibrvError> {
+        match request.get_reply_subject()? {
+            Some(subject) => reply.set_send_subject(&subject)?,
+            None => Err(ErrorKind::NotARequest)?,
+        }
+        self.send(reply)
+    }
+
+    /// Listen on this subject and respond to requests using the
+    /// supplied closure.
+    ///
+    /// See [`Transport::serve`] for details.
+    ///
+    /// [`Transport::serve`]: struct.Transport.html#method.serve
+    fn serve<F>(&self, subject: &str, f: F) -> Result<(), TibrvError>
+    where
+        F: Fn(&Msg) -> Result<Msg, TibrvError>,
+    {
+        let sub = self.subscribe(subject)?;
+        loop {
+            let request = sub.next()?;
+            // Messages which aren't requests can't be answered.
+            if request.get_reply_subject()?.is_none() {
+                continue;
+            }
+            let mut reply = f(&request)?;
+            self.send_reply(&mut reply, &request)?
+        }
+    }
+}
 
 /// A struct representing a Rendezvous transport object.
 ///
@@ -49,7 +127,41 @@ use tokio::reactor::Handle;
 ///  [1]: https://docs.tibco.com/pub/rv_zos/8.4.5/doc/pdf/TIB_rv_concepts.pdf
 pub struct Transport {
     pub(crate) inner: tibrvTransport,
-    context: RvCtx,
+    pub(crate) context: RvCtx,
+    // The intra-process transport is owned by Rendezvous itself,
+    // so only transports we created are destroyed on drop.
+    owned: bool,
+    // Whether the transport is in timer batch mode, needed to
+    // restore the mode after a `flush_batch`.
+    #[cfg(feature = "tibrv_8_4")]
+    timer_batch: AtomicBool,
+    // Messages passed to the `Sink`, waiting to be sent together
+    // by `poll_complete`.
+    #[cfg(feature = "async")]
+    unsent: Mutex<Vec<Msg>>,
+}
+
+/// The batch mode of a transport, see [`Transport::set_batch_mode`].
+///
+/// [`Transport::set_batch_mode`]: struct.Transport.html#method.set_batch_mode
+#[cfg(feature = "tibrv_8_4")]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum BatchMode {
+    /// Send each outbound message as soon as possible.
+    Default,
+    /// Accumulate outbound messages, and send them in batches at
+    /// timer intervals, or once the batch size is reached.
+    Timer,
+}
+
+#[cfg(feature = "tibrv_8_4")]
+impl From<BatchMode> for tibrvTransportBatchMode {
+    fn from(mode: BatchMode) -> Self {
+        match mode {
+            BatchMode::Default => TIBRV_TRANSPORT_DEFAULT_BATCH,
+            BatchMode::Timer => TIBRV_TRANSPORT_TIMER_BATCH,
+        }
+    }
 }
 
 /// A builder for a Rendezvous transport object.
@@ -57,6 +169,10 @@ pub struct TransportBuilder {
     service: Option<CString>,
     daemon: Option<CString>,
     network: Option<CString>,
+    #[cfg(feature = "tibrv_8_4")]
+    batch_mode: Option<BatchMode>,
+    #[cfg(feature = "tibrv_8_4")]
+    batch_size: Option<u32>,
     context: RvCtx,
 }
 
@@ -75,6 +191,10 @@ impl TransportBuilder {
             service: None,
             daemon: None,
             network: None,
+            #[cfg(feature = "tibrv_8_4")]
+            batch_mode: None,
+            #[cfg(feature = "tibrv_8_4")]
+            batch_size: None,
             context: ctx,
         }
     }
@@ -96,6 +216,28 @@ impl TransportBuilder {
         Ok(self)
     }
 
+    /// Sets the batch mode of the transport.
+    ///
+    /// See [`Transport::set_batch_mode`] for details.
+    ///
+    /// [`Transport::set_batch_mode`]: struct.Transport.html#method.set_batch_mode
+    #[cfg(feature = "tibrv_8_4")]
+    pub fn with_batch_mode(mut self, mode: BatchMode) -> Self {
+        self.batch_mode = Some(mode);
+        self
+    }
+
+    /// Sets the batch size of the transport, in bytes.
+    ///
+    /// See [`Transport::set_batch_size`] for details.
+    ///
+    /// [`Transport::set_batch_size`]: struct.Transport.html#method.set_batch_size
+    #[cfg(feature = "tibrv_8_4")]
+    pub fn with_batch_size(mut self, bytes: u32) -> Self {
+        self.batch_size = Some(bytes);
+        self
+    }
+
     /// Consumes the `TransportBuilder`, creating a `Transport`.
     pub fn create(self) -> Result<Transport, TibrvError> {
         // 0 is a bogus value, but we need to convince the compiler transport
@@ -111,10 +253,19 @@ impl TransportBuilder {
                 self.daemon.as_ref().map_or(null(), |d| d.as_ptr()),
             )
         };
-        result.map(|_| Transport {
-            inner: transport,
-            context: ctx,
-        })
+        let transport = result.map(|_| Transport::from_raw(transport, ctx, true))?;
+
+        #[cfg(feature = "tibrv_8_4")]
+        {
+            if let Some(mode) = self.batch_mode {
+                transport.set_batch_mode(mode)?;
+            }
+            if let Some(bytes) = self.batch_size {
+                transport.set_batch_size(bytes)?;
+            }
+        }
+
+        Ok(transport)
     }
 }
 
@@ -157,6 +308,135 @@ impl Drop for RvCtx {
 }
 
 impl Transport {
+    fn from_raw(inner: tibrvTransport, context: RvCtx, owned: bool) -> Transport {
+        Transport {
+            inner,
+            context,
+            owned,
+            #[cfg(feature = "tibrv_8_4")]
+            timer_batch: AtomicBool::new(false),
+            #[cfg(feature = "async")]
+            unsent: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Get a handle to the intra-process transport.
+    ///
+    /// Rendezvous automatically creates this transport when the
+    /// machinery is opened, it delivers messages only between listeners
+    /// within the current process, and does not require a daemon.
+    ///
+    /// It is useful for communicating between threads, or for testing
+    /// programs without access to a running `rvd`.
+    pub fn intra_process(ctx: &RvCtx) -> Transport {
+        Transport::from_raw(TIBRV_PROCESS_TRANSPORT, ctx.clone(), false)
+    }
+
+    /// Create the accepting end of a virtual circuit.
+    ///
+    /// Virtual circuits provide point-to-point delivery between exactly
+    /// two programs, over an existing network transport `tp`. Returns the
+    /// virtual circuit transport, along with the connect subject which
+    /// the other program must pass to `connect_vc`.
+    ///
+    /// The returned transport cannot carry messages until the connection
+    /// is complete, see `wait_for_vc_connection`.
+    pub fn accept_vc(tp: &Transport) -> Result<(Transport, String), TibrvError> {
+        let mut transport: tibrvTransport = 0;
+        let mut subject: *const ::std::os::raw::c_char = null();
+        unsafe {
+            tibrvTransport_CreateAcceptVc(&mut transport, &mut subject, tp.inner).map(|_| {
+                (
+                    Transport::from_raw(transport, tp.context.clone(), true),
+                    CStr::from_ptr(subject).to_string_lossy().into_owned(),
+                )
+            })
+        }
+    }
+
+    /// Create the connecting end of a virtual circuit.
+    ///
+    /// `connect_subject` is the subject returned by `accept_vc` in the
+    /// other program, which must be reachable over the network transport
+    /// `tp`.
+    pub fn connect_vc(tp: &Transport, connect_subject: &str) -> Result<Transport, TibrvError> {
+        let subject = CString::new(connect_subject).context(ErrorKind::StrContentError)?;
+        let mut transport: tibrvTransport = 0;
+        unsafe { tibrvTransport_CreateConnectVc(&mut transport, subject.as_ptr(), tp.inner) }
+            .map(|_| Transport::from_raw(transport, tp.context.clone(), true))
+    }
+
+    /// Block until this virtual circuit transport is connected, or
+    /// `timeout` has elapsed.
+    ///
+    /// A `None` parameter for `timeout` means block indefinitely. If the
+    /// connection is not complete in time, an error of kind `Timeout` is
+    /// returned.
+    pub fn wait_for_vc_connection(&self, timeout: Option<Duration>) -> Result<(), TibrvError> {
+        let timeout = timeout.map_or(-1.0, |t| t.as_secs_f64());
+        unsafe { tibrvTransport_WaitForVcConnection(self.inner, timeout) }.map(|_| ())
+    }
+
+    /// Get the connection state of this virtual circuit transport.
+    ///
+    /// Fails if this is not a virtual circuit transport.
+    pub fn vc_state(&self) -> Result<VcState, TibrvError> {
+        match unsafe { tibrvTransport_WaitForVcConnection(self.inner, 0.0) } {
+            TIBRV_OK => Ok(VcState::Connected),
+            TIBRV_TIMEOUT | TIBRV_VC_NOT_CONNECTED => Ok(VcState::Disconnected),
+            status => Err(ErrorKind::from(status))?,
+        }
+    }
+
+    /// Observe changes in the connection state of this virtual circuit
+    /// transport.
+    ///
+    /// Subscribes to the connected and disconnected advisories which
+    /// Rendezvous publishes on the virtual circuit.
+    pub fn vc_monitor(&self) -> Result<VcMonitor, TibrvError> {
+        self.subscribe(VC_ADVISORY_SUBJECT)
+            .map(|sub| VcMonitor { sub })
+    }
+
+    /// Set the batch mode of the transport.
+    ///
+    /// In `BatchMode::Timer`, outbound messages are accumulated and sent
+    /// in batches, trading latency for throughput. Use `flush_batch` to send
+    /// any accumulated messages immediately.
+    #[cfg(feature = "tibrv_8_4")]
+    pub fn set_batch_mode(&self, mode: BatchMode) -> Result<(), TibrvError> {
+        unsafe { tibrvTransport_SetBatchMode(self.inner, mode.into()) }.map(|_| {
+            self.timer_batch
+                .store(mode == BatchMode::Timer, Ordering::SeqCst)
+        })
+    }
+
+    /// Set the number of bytes of outbound messages to accumulate
+    /// before sending a batch in `BatchMode::Timer`.
+    #[cfg(feature = "tibrv_8_4")]
+    pub fn set_batch_size(&self, bytes: u32) -> Result<(), TibrvError> {
+        unsafe { tibrvTransport_SetBatchSize(self.inner, bytes) }.map(|_| ())
+    }
+
+    /// Send any outbound messages accumulated in `BatchMode::Timer`.
+    ///
+    /// Named to avoid confusion with `Sink::flush` when using the async
+    /// feature.
+    ///
+    /// Rendezvous has no explicit flush. Leaving `BatchMode::Timer` sends
+    /// the pending batch, so the transport is switched to
+    /// `BatchMode::Default` and back again, and later messages are
+    /// batched as before. Does nothing if the transport is not in
+    /// `BatchMode::Timer`.
+    #[cfg(feature = "tibrv_8_4")]
+    pub fn flush_batch(&self) -> Result<(), TibrvError> {
+        if !self.timer_batch.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        self.set_batch_mode(BatchMode::Default)?;
+        self.set_batch_mode(BatchMode::Timer)
+    }
+
     /// Extract the daemon parameter from the transport.
     pub fn daemon(&self) -> Result<String, TibrvError> {
         let mut ptr: *const ::std::os::raw::c_char = unsafe { ::std::mem::zeroed() };
@@ -211,6 +491,32 @@ impl Transport {
         unsafe { tibrvTransport_Send(self.inner, msg.inner) }.map(|_| ())
     }
 
+    /// Send several messages through this transport in a single call.
+    ///
+    /// This is more efficient than calling `send` for each message when
+    /// publishing bursts of messages, each message must have its send
+    /// subject set.
+    pub fn send_many(&self, msgs: &mut [Msg]) -> Result<(), TibrvError> {
+        if msgs.is_empty() {
+            return Ok(());
+        }
+        let mut vector: Vec<tibrvMsg> = msgs.iter().map(|m| m.inner).collect();
+        unsafe { tibrvTransport_Sendv(self.inner, vector.as_mut_ptr(), vector.len() as tibrv_u32) }
+            .map(|_| ())
+    }
+
+    /// Send `reply` in response to `request`.
+    ///
+    /// The send subject of `reply` is taken from the reply subject of
+    /// `request` by Rendezvous. Fails with `ErrorKind::NotARequest` if
+    /// `request` has no reply subject.
+    pub fn send_reply(&self, reply: &mut Msg, request: &Msg) -> Result<(), TibrvError> {
+        if request.get_reply_subject()?.is_none() {
+            Err(ErrorKind::NotARequest)?
+        }
+        unsafe { tibrvTransport_SendReply(self.inner, reply.inner, request.inner) }.map(|_| ())
+    }
+
     /// Subscribe to a message subject.
     ///
     /// Sets up a Rendezvous message queue, along with a callback which
@@ -226,134 +532,389 @@ impl Transport {
     /// Send a synchronous request on the given subject, blocking until
     /// a response is received or `timeout` seconds have elapsed.
     ///
-    /// A `None` parameter for `timeout` means block indefinitely.
-    pub fn request(
-        &self,
-        msg: &mut Msg,
-        timeout: Option<f64>,
-    ) -> Result<Msg, TibrvError> {
+    /// A `None` parameter for `timeout` means block indefinitely. If no
+    /// response arrives in time, an error of kind `Timeout` is returned.
+    pub fn request(&self, msg: &mut Msg, timeout: Option<f64>) -> Result<Msg, TibrvError> {
         let mut ptr: tibrvMsg = unsafe { mem::zeroed() };
         unsafe {
-            tibrvTransport_SendRequest(
-                self.inner,
-                msg.inner,
-                &mut ptr,
-                timeout.unwrap_or(-1.0),
-            ).map(|_| Msg { inner: ptr })
+            tibrvTransport_SendRequest(self.inner, msg.inner, &mut ptr, timeout.unwrap_or(-1.0))
+                .map(|_| Msg { inner: ptr })
         }
     }
 
+    /// Send a request, collecting the replies from every responder.
+    ///
+    /// Publishes `msg` with an inbox as its reply subject, then collects
+    /// every reply which arrives within `window`, up to `max_replies`.
+    /// Returns early once `max_replies` have been received, and an empty
+    /// `Vec` if there were no responders.
+    ///
+    /// This is useful for discovering the servers listening on a subject,
+    /// or for reading from a quorum of servers.
+    pub fn gather(
+        &self,
+        msg: &mut Msg,
+        window: Duration,
+        max_replies: usize,
+    ) -> Result<Vec<Msg>, TibrvError> {
+        let inbox = self.create_inbox()?;
+        let sub = self.subscribe(&inbox)?;
+        msg.set_reply_subject(&inbox)?;
+        self.send(msg)?;
+
+        let deadline = Instant::now() + window;
+        let mut replies = Vec::new();
+        while replies.len() < max_replies {
+            if let Ok(reply) = sub.queue().try_recv() {
+                replies.push(reply);
+                continue;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            match sub.queue().timed_dispatch(deadline - now) {
+                Err(ref e) if e.kind() == ErrorKind::Timeout => break,
+                result => result?,
+            }
+        }
+        Ok(replies)
+    }
+
     /// Listen on this subject and respond to requests using the
     /// supplied closure.
     ///
-    /// The closure consumes the incoming request, and you may either re-use
-    /// the same structure or create a new `Msg` for your response.
+    /// The closure borrows the incoming request and returns a new `Msg`
+    /// for your response, which is sent with `send_reply`. Use
+    /// `Msg::try_clone` to reply with a copy of the request.
     ///
-    /// If `reply_subject` is not set on the received message (i.e it is a
-    /// plain message rather than a request), it is not processed and is
-    /// dropped.
+    /// Messages without a `reply_subject` (i.e plain messages rather than
+    /// requests) are skipped.
     pub fn serve<F>(&self, subject: &str, f: F) -> Result<(), TibrvError>
     where
-        F: Fn(Msg) -> Result<Msg, TibrvError>,
+        F: Fn(&Msg) -> Result<Msg, TibrvError>,
+    {
+        RvTransport::serve(self, subject, f)
+    }
+
+    /// Start a streamed reply to `request`.
+    ///
+    /// Fails with `ErrorKind::NotARequest` if `request` has no reply
+    /// subject. See [`ReplyStream`] for details.
+    ///
+    /// [`ReplyStream`]: struct.ReplyStream.html
+    pub fn reply_stream(&self, request: &Msg) -> Result<ReplyStream<'_>, TibrvError> {
+        match request.get_reply_subject()? {
+            Some(subject) => Ok(ReplyStream::new(self, subject)),
+            None => Err(ErrorKind::NotARequest)?,
+        }
+    }
+
+    /// Listen on this subject and respond to each request with a stream
+    /// of replies, using the supplied closure.
+    ///
+    /// The closure consumes the incoming request, and sends any number of
+    /// replies through the `ReplyStream`. Once it returns, the end of the
+    /// stream is marked. If the closure fails, serving stops and the end
+    /// of the stream is not marked.
+    ///
+    /// As with `serve`, messages without a reply subject are skipped.
+    pub fn serve_stream<F>(&self, subject: &str, f: F) -> Result<(), TibrvError>
+    where
+        F: Fn(Msg, &mut ReplyStream) -> Result<(), TibrvError>,
     {
         let sub = self.subscribe(subject)?;
         loop {
-            let incoming = sub.next()?;
-            let reply_subj = incoming.get_reply_subject()?;
+            let request = sub.next()?;
+            let mut replies = match request.get_reply_subject()? {
+                Some(subject) => ReplyStream::new(self, subject),
+                None => continue,
+            };
+            f(request, &mut replies)?;
+            replies.finish()?
+        }
+    }
 
-            if reply_subj.is_some() {
-                let mut reply = f(incoming)?;
-                reply.set_send_subject(&reply_subj.unwrap())?;
-                self.send(&mut reply)?
+    #[cfg(feature = "async")]
+    /// Asynchronously send a request which is answered with a stream of
+    /// replies, such as one served by `serve_stream`.
+    ///
+    /// Returns an `AsyncReplyStream` of the replies, which fails with an
+    /// error of kind `Timeout` if no reply arrives for `idle_timeout`.
+    pub fn async_request_stream(
+        &self,
+        msg: &mut Msg,
+        idle_timeout: Duration,
+    ) -> Result<AsyncReplyStream, TibrvError> {
+        AsyncQueue::new(self.context.clone())?.request_stream(self, msg, idle_timeout)
+    }
+
+    #[cfg(feature = "async")]
+    /// Asynchronously listen on this subject and respond to requests using
+    /// the supplied closure.
+    ///
+    /// As with `serve`, messages without a reply subject are skipped, and
+    /// serving stops at the first error from `f`. See `r#async::ServerBuilder` for a server which
+    /// answers errors with error replies, and can be shut down.
+    pub async fn async_serve<F, G>(self, subject: &str, f: F) -> Result<(), TibrvError>
+    where
+        F: Fn(&Msg) -> G,
+        G: Future<Output = Result<Msg, TibrvError>>,
+    {
+        let mut sub = self.async_sub(subject)?;
+        while let Some(request) = sub.next().await {
+            let request = request?;
+            if request.get_reply_subject()?.is_none() {
+                continue;
             }
+            let mut reply = f(&request).await?;
+            self.send_reply(&mut reply, &request)?;
         }
+        Ok(())
     }
 
     #[cfg(feature = "tokio")]
-    pub fn async_serve<F, G>(
-        self,
-        handle: &Handle,
-        subject: &str,
-        f: F,
-    ) -> impl Future<Item = (), Error = TibrvError>
+    /// Spawn `async_serve` onto the current Tokio runtime.
+    ///
+    /// Returns a handle to the spawned task, which resolves once serving
+    /// stops. Must be called from within a Tokio runtime.
+    pub fn spawn_serve<F, G>(self, subject: &str, f: F) -> JoinHandle<Result<(), TibrvError>>
     where
-        F: Fn(Msg) -> G,
-        G: IntoFuture<Item = Msg, Error = TibrvError>,
+        F: Fn(&Msg) -> G + Send + 'static,
+        G: Future<Output = Result<Msg, TibrvError>> + Send + 'static,
     {
-        let sub = self.async_sub(handle, subject).unwrap();
-
-        sub.and_then(move |msg| AsyncReply {
-            subject: msg.get_reply_subject().unwrap().unwrap(),
-            future: f(msg).into_future(),
-        }).forward(self)
-            .then(|_| Ok(()))
+        let subject = subject.to_owned();
+        tokio::spawn(async move { self.async_serve(&subject, f).await })
     }
 
-    #[cfg(feature = "tokio")]
+    #[cfg(feature = "async")]
     /// Asynchronously subscribe to a message subject.
     ///
     /// Sets up the queue and channels as in a synchronous subscription, and
     /// returns an `AsyncSub` stream.
-    pub fn async_sub(
-        &self,
-        handle: &Handle,
-        subject: &str,
-    ) -> Result<AsyncSub, TibrvError> {
-        AsyncQueue::new(self.context.clone())?.subscribe(handle, &self, subject)
+    pub fn async_sub(&self, subject: &str) -> Result<AsyncSub, TibrvError> {
+        AsyncQueue::new(self.context.clone())?.subscribe(&self, subject)
     }
 
-    #[cfg(feature = "tokio")]
+    #[cfg(feature = "async")]
     /// Asynchronously send a request on the given subject.
     ///
     /// Returns an `AsyncReq` future representing the response.
-    pub fn async_req(
+    pub fn async_req(&self, msg: &mut Msg) -> Result<AsyncReq, TibrvError> {
+        AsyncQueue::new(self.context.clone())?.request(self, msg, None)
+    }
+
+    #[cfg(feature = "async")]
+    /// Asynchronously send a request on the given subject, failing if no
+    /// response is received within `timeout`.
+    ///
+    /// Returns an `AsyncReq` future representing the response, which
+    /// resolves to an error of kind `Timeout` if the timeout expires.
+    pub fn async_req_timeout(
         &self,
-        handle: &Handle,
         msg: &mut Msg,
+        timeout: Duration,
     ) -> Result<AsyncReq, TibrvError> {
-        let inbox = self.create_inbox()?;
-        let sub = self.async_sub(handle, &inbox)?;
+        AsyncQueue::new(self.context.clone())?.request(self, msg, Some(timeout))
+    }
 
-        msg.set_reply_subject(&inbox)?;
-        self.send(msg)?;
+    #[cfg(feature = "async")]
+    /// Asynchronously send a request, collecting the replies from every
+    /// responder.
+    ///
+    /// Returns an `AsyncGather` stream of the replies, see `gather` for
+    /// details.
+    pub fn async_gather(
+        &self,
+        msg: &mut Msg,
+        window: Duration,
+        max_replies: usize,
+    ) -> Result<AsyncGather, TibrvError> {
+        AsyncQueue::new(self.context.clone())?.gather(self, msg, window, max_replies)
+    }
+}
+
+impl RvTransport for Transport {
+    type Subscription = Subscription;
+
+    fn send(&self, msg: &mut Msg) -> Result<(), TibrvError> {
+        Transport::send(self, msg)
+    }
+
+    fn subscribe(&self, subject: &str) -> Result<Subscription, TibrvError> {
+        Transport::subscribe(self, subject)
+    }
+
+    fn create_inbox(&self) -> Result<String, TibrvError> {
+        Transport::create_inbox(self)
+    }
 
-        Ok(AsyncReq::new(sub))
+    fn request(&self, msg: &mut Msg, timeout: Option<f64>) -> Result<Msg, TibrvError> {
+        Transport::request(self, msg, timeout)
+    }
+
+    fn send_reply(&self, reply: &mut Msg, request: &Msg) -> Result<(), TibrvError> {
+        Transport::send_reply(self, reply, request)
     }
 }
 
 impl Drop for Transport {
     fn drop(&mut self) {
-        unsafe { tibrvTransport_Destroy(self.inner) };
+        if self.owned {
+            unsafe { tibrvTransport_Destroy(self.inner) };
+        }
     }
 }
 
-#[cfg(feature = "tokio")]
-impl Sink for Transport {
-    type SinkItem = Msg;
-    type SinkError = TibrvError;
+// Fields which sequence the replies in a `ReplyStream`.
+pub(crate) const STREAM_SEQ_FIELD: &str = "_seq";
+pub(crate) const STREAM_END_FIELD: &str = "_eos";
+
+/// Sends a stream of replies to a single request, created by
+/// [`Transport::reply_stream`].
+///
+/// Each reply carries a `u32` field named `_seq`, numbering the replies
+/// from zero, so that the receiver can detect any which are lost. The end
+/// of the stream is marked by a final reply, containing only `_seq` and a
+/// `bool` field named `_eos`.
+///
+/// [`Transport::reply_stream`]: struct.Transport.html#method.reply_stream
+pub struct ReplyStream<'a> {
+    tp: &'a Transport,
+    reply_subject: String,
+    seq: u32,
+}
+
+impl<'a> ReplyStream<'a> {
+    fn new(tp: &'a Transport, reply_subject: String) -> Self {
+        ReplyStream {
+            tp,
+            reply_subject,
+            seq: 0,
+        }
+    }
+
+    /// Send the next reply in the stream.
+    pub fn send(&mut self, reply: &mut Msg) -> Result<(), TibrvError> {
+        // The same message may be reused for each reply.
+        let _ = reply.remove_field_by_name(STREAM_SEQ_FIELD);
+        let mut seq = Builder::new(&self.seq).with_name(STREAM_SEQ_FIELD).encode();
+        reply.add_field(&mut seq)?;
+        reply.set_send_subject(&self.reply_subject)?;
+        self.tp.send(reply)?;
+        self.seq += 1;
+        Ok(())
+    }
+
+    /// Mark the end of the stream.
+    pub fn finish(mut self) -> Result<(), TibrvError> {
+        let mut end = Msg::new()?;
+        let mut field = Builder::new(&true).with_name(STREAM_END_FIELD).encode();
+        end.add_field(&mut field)?;
+        self.send(&mut end)
+    }
+}
+
+// Matches both `_RV.INFO.SYSTEM.VC.CONNECTED` and
+// `_RV.ERROR.SYSTEM.VC.DISCONNECTED`.
+const VC_ADVISORY_SUBJECT: &str = "_RV.*.SYSTEM.VC.*";
+
+/// The connection state of a virtual circuit transport.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum VcState {
+    /// Both ends of the virtual circuit are connected, and messages
+    /// can be sent and received.
+    Connected,
+    /// The virtual circuit is not yet connected, or the connection has
+    /// been lost. A lost connection cannot be restored, a new virtual
+    /// circuit must be created.
+    Disconnected,
+}
+
+/// A stream of connection state changes on a virtual circuit transport,
+/// created by [`Transport::vc_monitor`].
+///
+/// [`Transport::vc_monitor`]: struct.Transport.html#method.vc_monitor
+pub struct VcMonitor {
+    sub: Subscription,
+}
+
+impl VcMonitor {
+    /// Block until the connection state changes.
+    pub fn next(&self) -> Result<VcState, TibrvError> {
+        loop {
+            if let Some(state) = vc_advisory(&self.sub.next()?)? {
+                return Ok(state);
+            }
+        }
+    }
+
+    /// Get the next connection state change, without blocking.
+    pub fn try_next(&self) -> Result<VcState, mpsc::TryRecvError> {
+        loop {
+            let msg = self.sub.try_next()?;
+            if let Ok(Some(state)) = vc_advisory(&msg) {
+                return Ok(state);
+            }
+        }
+    }
+}
+
+fn vc_advisory(msg: &Msg) -> Result<Option<VcState>, TibrvError> {
+    Ok(match msg.get_send_subject()? {
+        Some(ref s) if s.ends_with(".VC.CONNECTED") => Some(VcState::Connected),
+        Some(ref s) if s.ends_with(".VC.DISCONNECTED") => Some(VcState::Disconnected),
+        _ => None,
+    })
+}
+
+// The most messages the `Sink` buffers before sending them,
+// even if `poll_complete` has not been called.
+#[cfg(feature = "async")]
+const MAX_UNSENT: usize = 1024;
+
+#[cfg(feature = "async")]
+impl Sink<Msg> for Transport {
+    type Error = TibrvError;
 
     // libtibrv doesn't provide an explicit "async send" routine
     // From the documentation it looks like tibrvTransport_Send
     // isn't supposed to block, so we have to just assume it's
     // doing internal buffering.
-    fn start_send(
-        &mut self,
-        mut item: Msg,
-    ) -> StartSend<Self::SinkItem, Self::SinkError> {
-        // Here we do the send immediately, then always return
-        // complete when poll_complete is called later.
-        Transport::send(self, &mut item)?;
-        Ok(AsyncSink::Ready)
+    //
+    // Messages are buffered here, then sent with a single call to
+    // tibrvTransport_Sendv when poll_flush is called. If the send fails
+    // they stay buffered, to be retried by the next flush.
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        if self.unsent.get_mut().unwrap().len() >= MAX_UNSENT {
+            return self.poll_flush(cx);
+        }
+        Poll::Ready(Ok(()))
     }
 
-    fn poll_complete(&mut self) -> Poll<(), Self::SinkError> {
-        Ok(Async::Ready(()))
+    fn start_send(mut self: Pin<&mut Self>, item: Msg) -> Result<(), Self::Error> {
+        self.unsent.get_mut().unwrap().push(item);
+        Ok(())
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        let mut unsent = mem::take(self.unsent.get_mut().unwrap());
+        let result = self.send_many(&mut unsent);
+        if result.is_err() {
+            *self.unsent.get_mut().unwrap() = unsent;
+        }
+        Poll::Ready(result)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.poll_flush(cx)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_util::{reply, spawn_server};
 
     #[test]
     fn version() {
@@ -369,10 +930,9 @@ mod tests {
     }
 
     #[test]
-    #[ignore]
     fn create_inbox() {
         let ctx = RvCtx::new().unwrap();
-        let tp = TransportBuilder::new(ctx).create().unwrap();
+        let tp = Transport::intra_process(&ctx);
         let inbox = tp.create_inbox().unwrap();
         assert!(!inbox.is_empty());
         assert!(inbox.contains("INBOX"));
@@ -380,20 +940,286 @@ mod tests {
     }
 
     #[test]
-    #[ignore]
     fn timeout_request() {
         let ctx = RvCtx::new().unwrap();
-        let tp = TransportBuilder::new(ctx).create().unwrap();
+        let tp = Transport::intra_process(&ctx);
 
         let mut msg = Msg::new().unwrap();
         msg.set_send_subject("REQUEST.TEST").unwrap();
         let req = tp.request(&mut msg, Some(1.0));
         assert!(req.is_err());
-        let _ = req.map_err(|e| {
+        let _ = req.map_err(|e| assert_eq!(ErrorKind::Timeout, e.kind()));
+    }
+
+    #[test]
+    fn intra_process_send_recv() {
+        let ctx = RvCtx::new().unwrap();
+        let tp = Transport::intra_process(&ctx);
+        let sub = tp.subscribe("TEST.INTRA").unwrap();
+
+        let mut msg = Msg::new().unwrap();
+        msg.set_send_subject("TEST.INTRA").unwrap();
+        // Disambiguate from `Sink::send` when built with the async feature.
+        Transport::send(&tp, &mut msg).unwrap();
+
+        let recv = sub.next().unwrap();
+        assert_eq!("TEST.INTRA", recv.get_send_subject().unwrap().unwrap());
+    }
+
+    #[test]
+    fn intra_process_request() {
+        let ctx = RvCtx::new().unwrap();
+        let server = spawn_server(&ctx, "TEST.INTRA.REQUEST", reply);
+
+        let tp = Transport::intra_process(&ctx);
+        let mut msg = Msg::new().unwrap();
+        msg.set_send_subject("TEST.INTRA.REQUEST").unwrap();
+        assert!(tp.request(&mut msg, Some(5.0)).is_ok());
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn vc_advisory_subjects() {
+        let mut msg = Msg::new().unwrap();
+        msg.set_send_subject("_RV.INFO.SYSTEM.VC.CONNECTED")
+            .unwrap();
+        assert_eq!(Some(VcState::Connected), vc_advisory(&msg).unwrap());
+        msg.set_send_subject("_RV.ERROR.SYSTEM.VC.DISCONNECTED")
+            .unwrap();
+        assert_eq!(Some(VcState::Disconnected), vc_advisory(&msg).unwrap());
+        msg.set_send_subject("_RV.INFO.SYSTEM.HOST.STATUS").unwrap();
+        assert_eq!(None, vc_advisory(&msg).unwrap());
+    }
+
+    #[test]
+    fn vc_state_not_a_vc() {
+        let ctx = RvCtx::new().unwrap();
+        let tp = Transport::intra_process(&ctx);
+        assert!(tp.vc_state().is_err());
+    }
+
+    // Virtual circuits need a network transport.
+    #[test]
+    #[ignore] // Needs a Rendezvous daemon
+    fn virtual_circuit() {
+        let ctx = RvCtx::new().unwrap();
+        let tp = TransportBuilder::new(ctx).create().unwrap();
+
+        let (accept, subject) = Transport::accept_vc(&tp).unwrap();
+        // Monitor before connecting, so the connected advisory isn't missed.
+        let monitor = accept.vc_monitor().unwrap();
+        let connect = Transport::connect_vc(&tp, &subject).unwrap();
+
+        let timeout = Some(Duration::from_secs(10));
+        accept.wait_for_vc_connection(timeout).unwrap();
+        connect.wait_for_vc_connection(timeout).unwrap();
+        assert_eq!(VcState::Connected, accept.vc_state().unwrap());
+        assert_eq!(VcState::Connected, monitor.next().unwrap());
+
+        drop(connect);
+        assert_eq!(VcState::Disconnected, monitor.next().unwrap());
+    }
+
+    // Batching needs a network transport.
+    #[cfg(feature = "tibrv_8_4")]
+    #[test]
+    #[ignore] // Needs a Rendezvous daemon
+    fn batch_mode() {
+        let ctx = RvCtx::new().unwrap();
+        let recv = TransportBuilder::new(ctx.clone()).create().unwrap();
+        let sub = recv.subscribe("TEST.BATCH").unwrap();
+        let tp = TransportBuilder::new(ctx)
+            .with_batch_mode(BatchMode::Timer)
+            .with_batch_size(64 * 1024)
+            .create()
+            .unwrap();
+        assert!(tp.timer_batch.load(Ordering::SeqCst));
+
+        // A batch smaller than the batch size is still sent by the timer.
+        let mut msg = Msg::new().unwrap();
+        msg.set_send_subject("TEST.BATCH").unwrap();
+        Transport::send(&tp, &mut msg).unwrap();
+        sub.queue().timed_dispatch(Duration::from_secs(5)).unwrap();
+        assert!(sub.queue().try_recv().is_ok());
+
+        // Flushing sends the batch straight away and stays in timer mode.
+        Transport::send(&tp, &mut msg).unwrap();
+        tp.flush_batch().unwrap();
+        sub.queue()
+            .timed_dispatch(Duration::from_millis(100))
+            .unwrap();
+        assert!(sub.queue().try_recv().is_ok());
+        assert!(tp.timer_batch.load(Ordering::SeqCst));
+
+        tp.set_batch_mode(BatchMode::Default).unwrap();
+        assert!(!tp.timer_batch.load(Ordering::SeqCst));
+        Transport::send(&tp, &mut msg).unwrap();
+        sub.queue().timed_dispatch(Duration::from_secs(5)).unwrap();
+        assert!(sub.queue().try_recv().is_ok());
+    }
+
+    #[cfg(feature = "tibrv_8_4")]
+    #[test]
+    fn flush_batch_without_batching() {
+        let ctx = RvCtx::new().unwrap();
+        let tp = Transport::intra_process(&ctx);
+        let sub = tp.subscribe("TEST.FLUSH").unwrap();
+
+        // Messages are sent immediately, so flushing has nothing to do.
+        let mut msg = Msg::new().unwrap();
+        msg.set_send_subject("TEST.FLUSH").unwrap();
+        Transport::send(&tp, &mut msg).unwrap();
+        tp.flush_batch().unwrap();
+        assert!(!tp.timer_batch.load(Ordering::SeqCst));
+        assert!(sub.next().is_ok());
+    }
+
+    #[test]
+    fn send_many() {
+        let ctx = RvCtx::new().unwrap();
+        let tp = Transport::intra_process(&ctx);
+        let sub = tp.subscribe("TEST.SENDV.*").unwrap();
+
+        let mut msgs: Vec<Msg> = (0..3)
+            .map(|i| {
+                let mut msg = Msg::new().unwrap();
+                msg.set_send_subject(&format!("TEST.SENDV.{}", i)).unwrap();
+                msg
+            })
+            .collect();
+        tp.send_many(&mut msgs).unwrap();
+        tp.send_many(&mut []).unwrap();
+
+        for i in 0..3 {
+            let recv = sub.next().unwrap();
             assert_eq!(
-                ErrorKind::UnknownError(TIBRV_TIMEOUT),
-                e.kind()
-            )
-        });
+                format!("TEST.SENDV.{}", i),
+                recv.get_send_subject().unwrap().unwrap()
+            );
+        }
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn sink_buffers_until_complete() {
+        let ctx = RvCtx::new().unwrap();
+        let mut tp = Transport::intra_process(&ctx);
+        let sub = tp.subscribe("TEST.SINK").unwrap();
+
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut msg = Msg::new().unwrap();
+        msg.set_send_subject("TEST.SINK").unwrap();
+        assert!(Pin::new(&mut tp).poll_ready(&mut cx).is_ready());
+        Pin::new(&mut tp).start_send(msg).unwrap();
+        assert!(sub.try_next().is_err());
+
+        match Pin::new(&mut tp).poll_flush(&mut cx) {
+            Poll::Ready(result) => result.unwrap(),
+            Poll::Pending => panic!("Expected the flush to complete"),
+        }
+        assert!(sub.next().is_ok());
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn sink_keeps_messages_on_failure() {
+        let ctx = RvCtx::new().unwrap();
+        let mut tp = Transport::intra_process(&ctx);
+        let sub = tp.subscribe("TEST.SINK.FAIL").unwrap();
+
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        // Sendv fails on a message without a send subject.
+        Pin::new(&mut tp).start_send(Msg::new().unwrap()).unwrap();
+        let mut msg = Msg::new().unwrap();
+        msg.set_send_subject("TEST.SINK.FAIL").unwrap();
+        Pin::new(&mut tp).start_send(msg).unwrap();
+
+        for _ in 0..2 {
+            match Pin::new(&mut tp).poll_flush(&mut cx) {
+                Poll::Ready(result) => assert!(result.is_err()),
+                Poll::Pending => panic!("Expected the flush to complete"),
+            }
+            assert_eq!(2, tp.unsent.get_mut().unwrap().len());
+        }
+        assert!(sub.try_next().is_err());
+    }
+
+    #[test]
+    fn send_reply() {
+        let ctx = RvCtx::new().unwrap();
+        let tp = Transport::intra_process(&ctx);
+        let sub = tp.subscribe("TEST.REPLY").unwrap();
+
+        let mut request = Msg::new().unwrap();
+        request.set_send_subject("TEST.REPLY.REQUEST").unwrap();
+        request.set_reply_subject("TEST.REPLY").unwrap();
+        let mut reply = Msg::new().unwrap();
+        tp.send_reply(&mut reply, &request).unwrap();
+        assert_eq!(
+            "TEST.REPLY",
+            sub.next().unwrap().get_send_subject().unwrap().unwrap()
+        );
+    }
+
+    #[test]
+    fn send_reply_not_a_request() {
+        let ctx = RvCtx::new().unwrap();
+        let tp = Transport::intra_process(&ctx);
+
+        let mut request = Msg::new().unwrap();
+        request.set_send_subject("TEST.REPLY.REQUEST").unwrap();
+        let mut reply = Msg::new().unwrap();
+        match tp.send_reply(&mut reply, &request) {
+            Err(e) => assert_eq!(ErrorKind::NotARequest, e.kind()),
+            Ok(_) => panic!("Expected an error"),
+        }
+    }
+
+    #[test]
+    fn gather() {
+        let ctx = RvCtx::new().unwrap();
+        let servers = (0..3)
+            .map(|_| spawn_server(&ctx, "TEST.GATHER", reply))
+            .collect::<Vec<_>>();
+
+        let tp = Transport::intra_process(&ctx);
+        let mut msg = Msg::new().unwrap();
+        msg.set_send_subject("TEST.GATHER").unwrap();
+        let replies = tp.gather(&mut msg, Duration::from_secs(5), 3).unwrap();
+        assert_eq!(3, replies.len());
+        for server in servers {
+            server.join().unwrap();
+        }
+
+        // With no responders, the window elapses with no replies.
+        let replies = tp.gather(&mut msg, Duration::from_millis(50), 3).unwrap();
+        assert!(replies.is_empty());
+    }
+
+    #[test]
+    fn reply_stream() {
+        let ctx = RvCtx::new().unwrap();
+        let tp = Transport::intra_process(&ctx);
+        let sub = tp.subscribe("TEST.STREAM.INBOX").unwrap();
+
+        let mut request = Msg::new().unwrap();
+        request.set_reply_subject("TEST.STREAM.INBOX").unwrap();
+        let mut replies = tp.reply_stream(&request).unwrap();
+        let mut chunk = Msg::new().unwrap();
+        replies.send(&mut chunk).unwrap();
+        replies.send(&mut chunk).unwrap();
+        replies.finish().unwrap();
+
+        for i in 0..3 {
+            let msg = sub.next().unwrap();
+            let seq = msg.get_field_by_name(STREAM_SEQ_FIELD).unwrap();
+            assert_eq!(i, seq.try_decode::<u32>().unwrap());
+            assert_eq!(i == 2, msg.get_field_by_name(STREAM_END_FIELD).is_ok());
+        }
+
+        let not_a_request = Msg::new().unwrap();
+        assert!(tp.reply_stream(&not_a_request).is_err());
     }
 }
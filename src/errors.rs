@@ -4,17 +4,13 @@ use failure::*;
 use std::fmt;
 use tibrv_sys::{tibrv_status, tibrv_u8};
 use tibrv_sys::{
-    TIBRV_OK,
-    TIBRV_INIT_FAILURE, TIBRV_INVALID_TRANSPORT, TIBRV_SERVICE_NOT_FOUND,
-    TIBRV_NETWORK_NOT_FOUND, TIBRV_DAEMON_NOT_FOUND, TIBRV_DAEMON_NOT_CONNECTED,
+    TIBRV_CORRUPT_MSG, TIBRV_DAEMON_NOT_CONNECTED, TIBRV_DAEMON_NOT_FOUND, TIBRV_INIT_FAILURE,
+    TIBRV_INVALID_NAME, TIBRV_INVALID_SIZE, TIBRV_INVALID_TRANSPORT, TIBRV_NETWORK_NOT_FOUND,
+    TIBRV_OK, TIBRV_SERVICE_NOT_FOUND, TIBRV_TIMEOUT,
 };
 
-
 pub(crate) trait TibrvResult {
-    fn and_then<U, F: FnOnce(Self) -> Result<U, TibrvError>>(
-        self,
-        f: F,
-    ) -> Result<U, TibrvError>
+    fn and_then<U, F: FnOnce(Self) -> Result<U, TibrvError>>(self, f: F) -> Result<U, TibrvError>
     where
         Self: Sized;
     fn map<U, F: FnOnce(Self) -> U>(self, f: F) -> Result<U, TibrvError>
@@ -62,9 +58,26 @@ pub enum ErrorKind {
     /// There was an attempt to decode a MsgField with an unsupported TIBRVMSG_*
     /// value
     #[fail(
-        display = "Tried to decode a field with unknown/unsupported tag value {}", _0
+        display = "Tried to decode a field with unknown/unsupported tag value {}",
+        _0
     )]
     UnknownFieldTypeError(tibrv_u8),
+    /// A reply was attempted to a message which has no reply subject.
+    #[fail(display = "Message is not a request, it has no reply subject")]
+    NotARequest,
+    /// No response was received before the timeout expired, including
+    /// when Rendezvous returns `TIBRV_TIMEOUT`.
+    #[fail(display = "Timed out waiting for a response")]
+    Timeout,
+    /// A message in a sequence was lost, a later message arrived first.
+    #[fail(
+        display = "Sequence gap, expected message {} but received {}",
+        expected, received
+    )]
+    SequenceGap { expected: u32, received: u32 },
+    /// Message text could not be parsed, the position is 1-based.
+    #[fail(display = "Invalid message text at line {}, column {}", line, column)]
+    ParseError { line: u32, column: u32 },
     /// Some other Rendezvous error occurred.
     #[fail(display = "Unknown Error: {}", _0)]
     UnknownError(tibrv_status),
@@ -118,20 +131,35 @@ impl From<tibrv_status> for ErrorKind {
             | TIBRV_NETWORK_NOT_FOUND
             | TIBRV_DAEMON_NOT_FOUND
             | TIBRV_DAEMON_NOT_CONNECTED => ErrorKind::TransportError,
+            TIBRV_TIMEOUT => ErrorKind::Timeout,
             _ => ErrorKind::UnknownError(status),
         }
     }
 }
 
+impl From<tibrv_wire::Error> for ErrorKind {
+    fn from(err: tibrv_wire::Error) -> Self {
+        match err {
+            tibrv_wire::Error::Corrupt => ErrorKind::UnknownError(TIBRV_CORRUPT_MSG),
+            tibrv_wire::Error::UnknownFieldType(t) => ErrorKind::UnknownFieldTypeError(t),
+            tibrv_wire::Error::InvalidName => ErrorKind::UnknownError(TIBRV_INVALID_NAME),
+            tibrv_wire::Error::InvalidSize => ErrorKind::UnknownError(TIBRV_INVALID_SIZE),
+        }
+    }
+}
+
+impl From<tibrv_wire::Error> for TibrvError {
+    fn from(err: tibrv_wire::Error) -> TibrvError {
+        ErrorKind::from(err).into()
+    }
+}
+
 /// Allows easy mapping of `tibrv_error` return codes into
 /// `Result<U, TibrvError>` types.
 ///
 /// Executes supplied closure if the `tibrv_status` is not `TIBRV_OK`.
 impl TibrvResult for tibrv_status {
-    fn and_then<U, F: FnOnce(Self) -> Result<U, TibrvError>>(
-        self,
-        f: F,
-    ) -> Result<U, TibrvError> {
+    fn and_then<U, F: FnOnce(Self) -> Result<U, TibrvError>>(self, f: F) -> Result<U, TibrvError> {
         match self {
             TIBRV_OK => f(self),
             _ => Err(ErrorKind::from(self).into()),
@@ -1,16 +1,43 @@
 //! Interfaces for dealing with inbound events from Rendezvous
+//!
+//! Events are placed on a [`Queue`] as they occur, and are processed
+//! whenever the queue is dispatched. A single queue may host any number
+//! of subscriptions, timers and IO events, which are then dispatched
+//! together in order of arrival.
+//!
+//! ```no_run
+//! use tibrv::context::{RvCtx, Transport};
+//! use tibrv::event::Queue;
+//!
+//! let ctx = RvCtx::new().unwrap();
+//! let tp = Transport::intra_process(&ctx);
+//!
+//! let queue = Queue::new(ctx.clone()).unwrap();
+//! let _bids = queue.subscribe(&tp, "PRICES.*.BID").unwrap();
+//! let _asks = queue.subscribe(&tp, "PRICES.*.ASK").unwrap();
+//!
+//! // Messages from both subscriptions, in the order they arrived.
+//! let msg = queue.next().unwrap();
+//! ```
+//!
+//! [`Queue`]: struct.Queue.html
 
-use context::{RvCtx, Transport};
-use errors::*;
+use crate::context::{RvCtx, Transport};
+use crate::errors::*;
+use crate::message::{BorrowedMsg, Msg};
 use failure::*;
-use message::{BorrowedMsg, Msg};
-use std::ffi::CString;
+use std::any::Any;
+use std::cmp;
+use std::collections::VecDeque;
+use std::ffi::{CStr, CString};
 use std::mem;
-use std::sync::mpsc;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
 use tibrv_sys::*;
 
 unsafe extern "C" fn sync_callback(
-    _event: tibrvEvent,
+    event: tibrvEvent,
     message: tibrvMsg,
     closure: *mut ::std::os::raw::c_void,
 ) {
@@ -18,20 +45,286 @@ unsafe extern "C" fn sync_callback(
     // way to indicate that to Rendezvous without causing an abort.
     // Instead we catch any recoverable unwind.
     let _ = ::std::panic::catch_unwind(move || {
-        let sender = closure as *mut mpsc::Sender<Msg>;
+        let shared = &*(closure as *const QueueShared);
         let msg = BorrowedMsg { inner: message };
-        (&*sender).send(msg.detach().unwrap()).unwrap();
+        shared
+            .buffer
+            .lock()
+            .unwrap()
+            .push(event, msg.detach().unwrap());
+        shared.available.notify_all();
     });
 }
 
+// Used by timer and IO events, which carry no message, to signal
+// that the event occurred.
+unsafe extern "C" fn signal_callback(
+    _event: tibrvEvent,
+    _message: tibrvMsg,
+    closure: *mut ::std::os::raw::c_void,
+) {
+    let _ = ::std::panic::catch_unwind(move || {
+        let sender = closure as *mut mpsc::Sender<()>;
+        let _ = (&*sender).send(());
+    });
+}
+
+// Runs once a timer or IO event is destroyed and no callbacks remain in
+// progress, at which point the closure can be safely freed.
+unsafe extern "C" fn signal_complete(_event: tibrvEvent, closure: *mut ::std::os::raw::c_void) {
+    drop(Box::from_raw(closure as *mut mpsc::Sender<()>));
+}
+
+type Panic = Box<dyn Any + Send + 'static>;
+type Callback = Box<dyn FnMut(&BorrowedMsg) + Send>;
+
+// The closure of a `Listener`, owned by Rendezvous until the
+// listener is destroyed.
+struct ListenClosure {
+    callback: Mutex<Callback>,
+    panicked: Arc<Mutex<Option<Panic>>>,
+}
+
+unsafe extern "C" fn listen_callback(
+    _event: tibrvEvent,
+    message: tibrvMsg,
+    closure: *mut ::std::os::raw::c_void,
+) {
+    let closure = &*(closure as *const ListenClosure);
+    let msg = BorrowedMsg { inner: message };
+    // Unwinding into Rendezvous would abort, so a panic is stored
+    // and resumed once the dispatch call returns to Rust.
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut callback = closure.callback.lock().unwrap_or_else(|e| e.into_inner());
+        (*callback)(&msg)
+    }));
+    if let Err(payload) = result {
+        *closure.panicked.lock().unwrap() = Some(payload);
+    }
+}
+
+unsafe extern "C" fn listen_complete(_event: tibrvEvent, closure: *mut ::std::os::raw::c_void) {
+    drop(Box::from_raw(closure as *mut ListenClosure));
+}
+
+// Create an event which signals a channel each time it occurs.
+//
+// Ownership of the sender passes to Rendezvous, it is freed by
+// `signal_complete` once the event is destroyed.
+fn create_signal_event<F>(create: F) -> Result<(tibrvEvent, mpsc::Receiver<()>), TibrvError>
+where
+    F: FnOnce(*mut tibrvEvent, *const ::std::os::raw::c_void) -> tibrv_status,
+{
+    let (send, recv) = mpsc::channel();
+
+    let mut ptr: tibrvEvent = unsafe { mem::zeroed() };
+    let send_ptr = Box::into_raw(Box::new(send));
+    let result = create(&mut ptr, send_ptr as *const ::std::os::raw::c_void);
+    if result != TIBRV_OK {
+        drop(unsafe { Box::from_raw(send_ptr) });
+    }
+    result.map(|_| (ptr, recv))
+}
+
+// Dispatch the queue until the given channel has a value available.
+fn dispatch_until<T>(queue: &Queue, channel: &mpsc::Receiver<T>) -> Result<T, TibrvError> {
+    loop {
+        match channel.try_recv() {
+            Ok(v) => return Ok(v),
+            Err(mpsc::TryRecvError::Empty) => queue.dispatch()?,
+            Err(mpsc::TryRecvError::Disconnected) => Err(ErrorKind::QueueError)?,
+        }
+    }
+}
+
+/// A policy for discarding events once a queue reaches its limit.
+///
+/// With every policy except `DiscardNone`, `max_events` must be
+/// greater than zero, and `discard_amount` must be between one and
+/// `max_events`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum LimitPolicy {
+    /// Never discard events, the queue grows without limit.
+    DiscardNone,
+    /// Discard each new event which arrives while the queue is full.
+    DiscardNew {
+        max_events: u32,
+        discard_amount: u32,
+    },
+    /// Discard `discard_amount` of the oldest events in the queue.
+    DiscardFirst {
+        max_events: u32,
+        discard_amount: u32,
+    },
+    /// Discard `discard_amount` of the newest events in the queue.
+    DiscardLast {
+        max_events: u32,
+        discard_amount: u32,
+    },
+}
+
+impl LimitPolicy {
+    fn is_valid(&self) -> bool {
+        match *self {
+            LimitPolicy::DiscardNone => true,
+            LimitPolicy::DiscardNew {
+                max_events,
+                discard_amount,
+            }
+            | LimitPolicy::DiscardFirst {
+                max_events,
+                discard_amount,
+            }
+            | LimitPolicy::DiscardLast {
+                max_events,
+                discard_amount,
+            } => max_events > 0 && discard_amount > 0 && discard_amount <= max_events,
+        }
+    }
+
+    fn to_raw(self) -> (tibrvQueueLimitPolicy, u32, u32) {
+        match self {
+            LimitPolicy::DiscardNone => (TIBRVQUEUE_DISCARD_NONE, 0, 0),
+            LimitPolicy::DiscardNew {
+                max_events,
+                discard_amount,
+            } => (TIBRVQUEUE_DISCARD_NEW, max_events, discard_amount),
+            LimitPolicy::DiscardFirst {
+                max_events,
+                discard_amount,
+            } => (TIBRVQUEUE_DISCARD_FIRST, max_events, discard_amount),
+            LimitPolicy::DiscardLast {
+                max_events,
+                discard_amount,
+            } => (TIBRVQUEUE_DISCARD_LAST, max_events, discard_amount),
+        }
+    }
+
+    fn from_raw(
+        policy: tibrvQueueLimitPolicy,
+        max_events: u32,
+        discard_amount: u32,
+    ) -> Result<Self, TibrvError> {
+        match policy {
+            TIBRVQUEUE_DISCARD_NONE => Ok(LimitPolicy::DiscardNone),
+            TIBRVQUEUE_DISCARD_NEW => Ok(LimitPolicy::DiscardNew {
+                max_events,
+                discard_amount,
+            }),
+            TIBRVQUEUE_DISCARD_FIRST => Ok(LimitPolicy::DiscardFirst {
+                max_events,
+                discard_amount,
+            }),
+            TIBRVQUEUE_DISCARD_LAST => Ok(LimitPolicy::DiscardLast {
+                max_events,
+                discard_amount,
+            }),
+            _ => Err(ErrorKind::from(TIBRV_INVALID_ARG).into()),
+        }
+    }
+}
+
+// Holds dispatched messages until they are consumed from Rust, applying
+// a `LimitPolicy` of its own. Each message is tagged with the listener
+// which received it, so a `Subscription` can take only its own.
+struct MsgBuffer {
+    messages: VecDeque<(tibrvEvent, Msg)>,
+    limit: LimitPolicy,
+    discarded: u64,
+}
+
+impl MsgBuffer {
+    fn push(&mut self, listener: tibrvEvent, msg: Msg) {
+        let len = self.messages.len();
+        match self.limit {
+            LimitPolicy::DiscardNone => {}
+            LimitPolicy::DiscardNew { max_events, .. } => {
+                if len >= max_events as usize {
+                    self.discarded += 1;
+                    return;
+                }
+            }
+            LimitPolicy::DiscardFirst {
+                max_events,
+                discard_amount,
+            } => {
+                if len >= max_events as usize {
+                    let n = cmp::min(discard_amount as usize, len);
+                    self.messages.drain(..n);
+                    self.discarded += n as u64;
+                }
+            }
+            LimitPolicy::DiscardLast {
+                max_events,
+                discard_amount,
+            } => {
+                if len >= max_events as usize {
+                    let n = cmp::min(discard_amount as usize, len);
+                    self.messages.truncate(len - n);
+                    self.discarded += n as u64;
+                }
+            }
+        }
+        self.messages.push_back((listener, msg));
+    }
+
+    // Take the oldest message, from any listener if `listener` is `None`.
+    fn pop(&mut self, listener: Option<tibrvEvent>) -> Option<Msg> {
+        let index = match listener {
+            None => 0,
+            Some(listener) => self.messages.iter().position(|(l, _)| *l == listener)?,
+        };
+        self.messages.remove(index).map(|(_, msg)| msg)
+    }
+
+    // Drop any messages left by a listener which has been destroyed.
+    fn remove(&mut self, listener: tibrvEvent) {
+        self.messages.retain(|(l, _)| *l != listener);
+    }
+}
+
+struct QueueShared {
+    inner: tibrvQueue,
+    // Every listener on the queue is handed a pointer to this buffer,
+    // so messages are received in the order they were dispatched.
+    buffer: Mutex<MsgBuffer>,
+    // Signalled whenever a message is added to the buffer.
+    available: Condvar,
+    // A panic raised by a `Listener` callback, waiting to be resumed.
+    panicked: Arc<Mutex<Option<Panic>>>,
+    _context: RvCtx,
+}
+
+impl Drop for QueueShared {
+    fn drop(&mut self) {
+        unsafe {
+            tibrvQueue_DestroyEx(self.inner, None, ::std::ptr::null());
+        }
+    }
+}
+
 /// Struct representing a Rendezvous event queue.
 ///
-/// Represents a queue of events waiting for dispatch, at present
-/// only message queues are implemented, although the library supports
-/// IO (socket) events and arbitrary timers as well.
-pub(crate) struct Queue {
+/// Represents a queue of events waiting for dispatch, which may
+/// be messages, timers or IO (socket) events.
+///
+/// A queue can host many events, across any number of transports.
+/// Messages from every subscription on the queue are delivered through
+/// a single buffer, preserving their order of arrival. Reading from the
+/// `Queue` returns them in that order, while each `Subscription` reads
+/// only its own.
+///
+/// By default neither the Rendezvous queue nor the buffer of dispatched
+/// messages is limited in size, see `set_limit_policy` and
+/// `set_buffer_limit` to protect against slow consumers.
+///
+/// `Queue` is a handle, cloning it produces another handle to the same
+/// underlying queue. The queue is destroyed once every handle, and every
+/// event created on it, has been dropped.
+#[derive(Clone)]
+pub struct Queue {
     pub(crate) inner: tibrvQueue,
-    _context: RvCtx,
+    shared: Arc<QueueShared>,
 }
 
 impl Queue {
@@ -39,40 +332,246 @@ impl Queue {
     ///
     /// The supplied `RvCtx` must live at least as long as any created
     /// queues.
-    pub(crate) fn new(ctx: RvCtx) -> Result<Self, TibrvError> {
+    pub fn new(ctx: RvCtx) -> Result<Self, TibrvError> {
         let mut ptr: tibrvQueue = unsafe { mem::zeroed() };
         unsafe { tibrvQueue_Create(&mut ptr) }.map(|_| Queue {
             inner: ptr,
-            _context: ctx,
+            shared: Arc::new(QueueShared {
+                inner: ptr,
+                buffer: Mutex::new(MsgBuffer {
+                    messages: VecDeque::new(),
+                    limit: LimitPolicy::DiscardNone,
+                    discarded: 0,
+                }),
+                available: Condvar::new(),
+                panicked: Arc::new(Mutex::new(None)),
+                _context: ctx,
+            }),
         })
     }
 
     /// Get the number of events waiting in the queue.
-    #[allow(dead_code)]
-    pub(crate) fn count(&self) -> Result<u32, TibrvError> {
+    pub fn count(&self) -> Result<u32, TibrvError> {
         let mut ptr: u32 = 0;
         unsafe { tibrvQueue_GetCount(self.inner, &mut ptr) }.map(|_| ptr)
     }
 
+    /// Dispatch a single event, blocking until one is available.
+    pub fn dispatch(&self) -> Result<(), TibrvError> {
+        let status = unsafe { tibrvQueue_TimedDispatch(self.inner, -1.0) };
+        self.resume_panic();
+        status.map(|_| ())
+    }
+
+    /// Dispatch a single event, blocking for at most `timeout`.
+    ///
+    /// If no event arrives in time, an error of kind `Timeout` is
+    /// returned.
+    pub fn timed_dispatch(&self, timeout: Duration) -> Result<(), TibrvError> {
+        let status = unsafe { tibrvQueue_TimedDispatch(self.inner, timeout.as_secs_f64()) };
+        self.resume_panic();
+        status.map(|_| ())
+    }
+
+    /// Dispatch a single event if one is available, without blocking.
+    ///
+    /// Returns `true` if an event was dispatched.
+    pub fn poll(&self) -> Result<bool, TibrvError> {
+        let status = unsafe { tibrvQueue_TimedDispatch(self.inner, 0.0) };
+        self.resume_panic();
+        match status {
+            TIBRV_TIMEOUT => Ok(false),
+            status => status.map(|_| true),
+        }
+    }
+
+    // Resume a panic caught in a `Listener` callback on this queue.
+    fn resume_panic(&self) {
+        let payload = self.shared.panicked.lock().unwrap().take();
+        if let Some(payload) = payload {
+            panic::resume_unwind(payload);
+        }
+    }
+
+    /// Get the next message from any subscription on this queue, in
+    /// order of arrival.
+    ///
+    /// Dispatches events until a message is available.
+    pub fn next(&self) -> Result<Msg, TibrvError> {
+        self.next_from(None)
+    }
+
+    /// Get the next message from any subscription on this queue,
+    /// without blocking.
+    pub fn try_next(&self) -> Result<Msg, mpsc::TryRecvError> {
+        self.try_next_from(None)
+    }
+
+    /// Wait for the next message to be dispatched, without dispatching
+    /// the queue.
+    ///
+    /// Use this when the queue is dispatched by another thread, such
+    /// as a [`Dispatcher`](struct.Dispatcher.html).
+    pub fn recv(&self) -> Result<Msg, TibrvError> {
+        self.recv_from(None)
+    }
+
+    // The reads shared by `Queue` and `Subscription`, taking messages
+    // from any listener, or only from `listener`.
+    fn next_from(&self, listener: Option<tibrvEvent>) -> Result<Msg, TibrvError> {
+        loop {
+            match self.try_recv_from(listener) {
+                Ok(msg) => return Ok(msg),
+                Err(mpsc::TryRecvError::Empty) => self.dispatch()?,
+                Err(mpsc::TryRecvError::Disconnected) => Err(ErrorKind::QueueError)?,
+            }
+        }
+    }
+
+    fn try_next_from(&self, listener: Option<tibrvEvent>) -> Result<Msg, mpsc::TryRecvError> {
+        let _ = self.poll(); // Ignore this "error"
+        self.try_recv_from(listener)
+    }
+
+    fn recv_from(&self, listener: Option<tibrvEvent>) -> Result<Msg, TibrvError> {
+        self.resume_panic();
+        let mut buffer = self.shared.buffer.lock().unwrap();
+        loop {
+            if let Some(msg) = buffer.pop(listener) {
+                return Ok(msg);
+            }
+            buffer = self.shared.available.wait(buffer).unwrap();
+        }
+    }
+
+    fn try_recv_from(&self, listener: Option<tibrvEvent>) -> Result<Msg, mpsc::TryRecvError> {
+        self.shared
+            .buffer
+            .lock()
+            .unwrap()
+            .pop(listener)
+            .ok_or(mpsc::TryRecvError::Empty)
+    }
+
+    /// Wait at most `timeout` for the next message to be dispatched,
+    /// without dispatching the queue.
+    ///
+    /// If no message arrives in time, an error of kind `Timeout` is
+    /// returned.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<Msg, TibrvError> {
+        self.resume_panic();
+        let deadline = Instant::now() + timeout;
+        let mut buffer = self.shared.buffer.lock().unwrap();
+        loop {
+            if let Some(msg) = buffer.pop(None) {
+                return Ok(msg);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                Err(ErrorKind::Timeout)?
+            }
+            let available = &self.shared.available;
+            buffer = available.wait_timeout(buffer, deadline - now).unwrap().0;
+        }
+    }
+
+    /// Take the next message which has already been dispatched,
+    /// without dispatching the queue.
+    ///
+    /// This is useful when the queue is dispatched elsewhere, for example
+    /// as part of a `QueueGroup`.
+    pub fn try_recv(&self) -> Result<Msg, mpsc::TryRecvError> {
+        self.try_recv_from(None)
+    }
+
+    /// Get the limit policy of the Rendezvous queue.
+    pub fn limit_policy(&self) -> Result<LimitPolicy, TibrvError> {
+        let mut policy = TIBRVQUEUE_DISCARD_NONE;
+        let mut max_events: tibrv_u32 = 0;
+        let mut discard_amount: tibrv_u32 = 0;
+        unsafe {
+            tibrvQueue_GetLimitPolicy(
+                self.inner,
+                &mut policy,
+                &mut max_events,
+                &mut discard_amount,
+            )
+        }
+        .and_then(|_| LimitPolicy::from_raw(policy, max_events, discard_amount))
+    }
+
+    /// Limit the number of events waiting for dispatch in the
+    /// Rendezvous queue.
+    ///
+    /// Rendezvous discards events according to the policy once the limit
+    /// is reached, and signals each occurrence with a
+    /// `_RV.WARN.SYSTEM.QUEUE.LIMIT_EXCEEDED` advisory.
+    pub fn set_limit_policy(&self, policy: LimitPolicy) -> Result<(), TibrvError> {
+        let (policy, max_events, discard_amount) = policy.to_raw();
+        unsafe { tibrvQueue_SetLimitPolicy(self.inner, policy, max_events, discard_amount) }
+            .map(|_| ())
+    }
+
+    /// Get the limit policy of the buffer of dispatched messages.
+    pub fn buffer_limit(&self) -> LimitPolicy {
+        self.shared.buffer.lock().unwrap().limit
+    }
+
+    /// Limit the number of dispatched messages waiting to be consumed.
+    ///
+    /// Messages are moved from the Rendezvous queue into a buffer as
+    /// they are dispatched, where they wait for a call to `next`. Once
+    /// the limit is reached, messages are discarded from the buffer
+    /// according to the policy, and counted by `buffer_discarded`.
+    pub fn set_buffer_limit(&self, policy: LimitPolicy) -> Result<(), TibrvError> {
+        if !policy.is_valid() {
+            Err(ErrorKind::from(TIBRV_INVALID_ARG))?
+        }
+        self.shared.buffer.lock().unwrap().limit = policy;
+        Ok(())
+    }
+
+    /// Get the number of dispatched messages discarded from the buffer
+    /// because of its limit policy.
+    ///
+    /// Only the buffer's own discards are counted. Events discarded by
+    /// the Rendezvous queue under `set_limit_policy` are never
+    /// dispatched, so aren't seen here, watch for the
+    /// `_RV.WARN.SYSTEM.QUEUE.LIMIT_EXCEEDED` advisory instead.
+    pub fn buffer_discarded(&self) -> u64 {
+        self.shared.buffer.lock().unwrap().discarded
+    }
+
+    /// Get the dispatch priority of this queue.
+    pub fn priority(&self) -> Result<u32, TibrvError> {
+        let mut ptr: tibrv_u32 = 0;
+        unsafe { tibrvQueue_GetPriority(self.inner, &mut ptr) }.map(|_| ptr as u32)
+    }
+
+    /// Set the dispatch priority of this queue.
+    ///
+    /// When dispatched as part of a `QueueGroup`, events are taken from
+    /// the highest priority queue with events waiting. Queues of equal
+    /// priority are dispatched in turn. The default priority is 1, and
+    /// 0 is the lowest priority.
+    pub fn set_priority(&self, priority: u32) -> Result<(), TibrvError> {
+        unsafe { tibrvQueue_SetPriority(self.inner, priority as tibrv_u32) }.map(|_| ())
+    }
+
     /// Subscribe to a message subject.
     ///
     /// Sets up the callback to copy messages from the event
-    /// queue into a `mpsc::channel` for consumption from Rust.
+    /// queue into the queue's buffer for consumption from Rust.
     ///
     /// Requires a reference to a valid `Transport` on which to listen.
     ///
     /// Subject must be valid ASCII, wildcards are accepted, although
     /// a wildcard-only subject is not.
-    pub(crate) fn subscribe(
-        self,
-        tp: &Transport,
-        subject: &str,
-    ) -> Result<Subscription, TibrvError> {
-        let (send, recv) = mpsc::channel();
+    pub fn subscribe(&self, tp: &Transport, subject: &str) -> Result<Subscription, TibrvError> {
         let subject_c = CString::new(subject).context(ErrorKind::StrContentError)?;
 
         let mut ptr: tibrvEvent = unsafe { mem::zeroed() };
-        let send_ptr = Box::into_raw(Box::new(send.clone()));
+        let send_ptr = &*self.shared as *const QueueShared;
         unsafe {
             tibrvEvent_CreateListener(
                 &mut ptr,
@@ -82,60 +581,440 @@ impl Queue {
                 subject_c.as_ptr(),
                 send_ptr as *const ::std::os::raw::c_void,
             )
-        }.map(|_| Subscription {
+        }
+        .map(|_| Subscription {
             event: ptr,
-            queue: self,
-            channel: recv,
+            queue: self.clone(),
+        })
+    }
+
+    /// Subscribe to a message subject, handling each message with a closure.
+    ///
+    /// Unlike `subscribe`, messages are not copied out of Rendezvous
+    /// storage. The closure runs inside the Rendezvous callback whenever
+    /// the queue is dispatched, and is handed a `BorrowedMsg` which is
+    /// only valid for the duration of the call. Use `to_owned` to keep
+    /// a copy of the message.
+    ///
+    /// If the closure panics, the panic is resumed from the call which
+    /// dispatched the queue, or, when the queue is dispatched by a
+    /// `Dispatcher`, from the next call to `recv`.
+    ///
+    /// ```no_run
+    /// use tibrv::context::{RvCtx, Transport};
+    /// use tibrv::event::Queue;
+    /// use tibrv::message::BorrowedMsg;
+    ///
+    /// let ctx = RvCtx::new().unwrap();
+    /// let tp = Transport::intra_process(&ctx);
+    /// let queue = Queue::new(ctx.clone()).unwrap();
+    ///
+    /// let mut count = 0;
+    /// let _listener = queue
+    ///     .listen(&tp, "PRICES.>", move |msg: &BorrowedMsg| {
+    ///         count += 1;
+    ///         println!("{}: {:?}", count, msg.get_send_subject());
+    ///     })
+    ///     .unwrap();
+    ///
+    /// loop {
+    ///     queue.dispatch().unwrap();
+    /// }
+    /// ```
+    pub fn listen<F>(
+        &self,
+        tp: &Transport,
+        subject: &str,
+        callback: F,
+    ) -> Result<Listener, TibrvError>
+    where
+        F: FnMut(&BorrowedMsg) + Send + 'static,
+    {
+        let subject_c = CString::new(subject).context(ErrorKind::StrContentError)?;
+
+        let mut ptr: tibrvEvent = unsafe { mem::zeroed() };
+        let closure = Box::into_raw(Box::new(ListenClosure {
+            callback: Mutex::new(Box::new(callback)),
+            panicked: self.shared.panicked.clone(),
+        }));
+        let result = unsafe {
+            tibrvEvent_CreateListener(
+                &mut ptr,
+                self.inner,
+                Some(listen_callback),
+                tp.inner,
+                subject_c.as_ptr(),
+                closure as *const ::std::os::raw::c_void,
+            )
+        };
+        if result != TIBRV_OK {
+            drop(unsafe { Box::from_raw(closure) });
+        }
+        result.map(|_| Listener {
+            event: ptr,
+            queue: self.clone(),
+        })
+    }
+
+    /// Create a timer which fires repeatedly at the given interval.
+    ///
+    /// Sets up the callback to signal each timer event through a
+    /// `mpsc::channel` for consumption from Rust.
+    pub fn timer(&self, interval: Duration) -> Result<Timer, TibrvError> {
+        create_signal_event(|ptr, closure| unsafe {
+            tibrvEvent_CreateTimer(
+                ptr,
+                self.inner,
+                Some(signal_callback),
+                interval.as_secs_f64(),
+                closure,
+            )
+        })
+        .map(|(event, channel)| Timer {
+            event,
+            queue: self.clone(),
+            channel,
+        })
+    }
+
+    /// Watch a file descriptor (or socket) for an IO condition.
+    ///
+    /// Sets up the callback to signal each time the condition is
+    /// met through a `mpsc::channel` for consumption from Rust.
+    pub fn io(&self, source: i32, condition: IoCondition) -> Result<IoEvent, TibrvError> {
+        create_signal_event(|ptr, closure| unsafe {
+            tibrvEvent_CreateIO(
+                ptr,
+                self.inner,
+                Some(signal_callback),
+                source,
+                condition.into(),
+                closure,
+            )
+        })
+        .map(|(event, channel)| IoEvent {
+            event,
+            queue: self.clone(),
+            channel,
         })
     }
 }
 
-impl Drop for Queue {
+/// Struct representing a Rendezvous queue group.
+///
+/// A queue group allows several `Queue`s to be dispatched together,
+/// according to their priorities, from a single dispatch loop.
+pub struct QueueGroup {
+    inner: tibrvQueueGroup,
+    // Queues are kept alive for as long as they are in the group.
+    queues: Vec<Queue>,
+    _context: RvCtx,
+}
+
+impl QueueGroup {
+    /// Constructs a new, empty queue group.
+    pub fn new(ctx: RvCtx) -> Result<Self, TibrvError> {
+        let mut ptr: tibrvQueueGroup = unsafe { mem::zeroed() };
+        unsafe { tibrvQueueGroup_Create(&mut ptr) }.map(|_| QueueGroup {
+            inner: ptr,
+            queues: Vec::new(),
+            _context: ctx,
+        })
+    }
+
+    /// Add a queue to this group.
+    pub fn add(&mut self, queue: &Queue) -> Result<(), TibrvError> {
+        unsafe { tibrvQueueGroup_Add(self.inner, queue.inner) }
+            .map(|_| self.queues.push(queue.clone()))
+    }
+
+    /// Remove a queue from this group.
+    pub fn remove(&mut self, queue: &Queue) -> Result<(), TibrvError> {
+        unsafe { tibrvQueueGroup_Remove(self.inner, queue.inner) }
+            .map(|_| self.queues.retain(|q| q.inner != queue.inner))
+    }
+
+    /// Dispatch a single event from the highest priority queue with
+    /// events waiting, blocking until one is available.
+    pub fn dispatch(&self) -> Result<(), TibrvError> {
+        let status = unsafe { tibrvQueueGroup_TimedDispatch(self.inner, -1.0) };
+        self.resume_panic();
+        status.map(|_| ())
+    }
+
+    /// Dispatch a single event, blocking for at most `timeout`.
+    ///
+    /// If no event arrives in time, an error of kind `Timeout` is
+    /// returned.
+    pub fn timed_dispatch(&self, timeout: Duration) -> Result<(), TibrvError> {
+        let status = unsafe { tibrvQueueGroup_TimedDispatch(self.inner, timeout.as_secs_f64()) };
+        self.resume_panic();
+        status.map(|_| ())
+    }
+
+    /// Dispatch a single event if one is available, without blocking.
+    ///
+    /// Returns `true` if an event was dispatched.
+    pub fn poll(&self) -> Result<bool, TibrvError> {
+        let status = unsafe { tibrvQueueGroup_TimedDispatch(self.inner, 0.0) };
+        self.resume_panic();
+        match status {
+            TIBRV_TIMEOUT => Ok(false),
+            status => status.map(|_| true),
+        }
+    }
+
+    fn resume_panic(&self) {
+        for queue in &self.queues {
+            queue.resume_panic();
+        }
+    }
+}
+
+impl Drop for QueueGroup {
     fn drop(&mut self) {
         unsafe {
-            tibrvQueue_DestroyEx(self.inner, None, ::std::ptr::null());
+            tibrvQueueGroup_Destroy(self.inner);
+        }
+    }
+}
+
+/// A subscription which handles each message with a closure, created
+/// by [`Queue::listen`](struct.Queue.html#method.listen).
+///
+/// The listener is destroyed, and the closure dropped, when this
+/// is dropped.
+pub struct Listener {
+    event: tibrvEvent,
+    queue: Queue,
+}
+
+impl Listener {
+    /// Get the queue on which this listener's messages are dispatched.
+    pub fn queue(&self) -> &Queue {
+        &self.queue
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        unsafe {
+            tibrvEvent_DestroyEx(self.event, Some(listen_complete));
+        }
+    }
+}
+
+/// A `Queue` or `QueueGroup`, which can be dispatched by a `Dispatcher`.
+pub trait Dispatchable {
+    /// Get the Rendezvous handle of the queue or queue group.
+    fn dispatchable(&self) -> tibrvDispatchable;
+}
+
+impl Dispatchable for Queue {
+    fn dispatchable(&self) -> tibrvDispatchable {
+        self.inner
+    }
+}
+
+impl Dispatchable for QueueGroup {
+    fn dispatchable(&self) -> tibrvDispatchable {
+        self.inner
+    }
+}
+
+/// A builder for a `Dispatcher`.
+pub struct DispatcherBuilder<D: Dispatchable> {
+    dispatchable: D,
+    name: Option<CString>,
+    timeout: Option<Duration>,
+}
+
+impl<D: Dispatchable> DispatcherBuilder<D> {
+    /// Constructs a new DispatcherBuilder for a `Queue` or `QueueGroup`.
+    ///
+    /// By default the dispatcher thread runs until the `Dispatcher`
+    /// is dropped.
+    pub fn new(dispatchable: D) -> Self {
+        DispatcherBuilder {
+            dispatchable,
+            name: None,
+            timeout: None,
+        }
+    }
+
+    /// Sets the name of the dispatcher thread, which appears in
+    /// Rendezvous diagnostics.
+    pub fn with_name(mut self, name: &str) -> Result<Self, TibrvError> {
+        self.name = Some(CString::new(name).context(ErrorKind::StrContentError)?);
+        Ok(self)
+    }
+
+    /// Sets an idle timeout, the dispatcher thread exits if no event
+    /// is dispatched within `timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Consumes the `DispatcherBuilder`, starting the dispatcher thread.
+    pub fn create(self) -> Result<Dispatcher<D>, TibrvError> {
+        let mut ptr: tibrvDispatcher = unsafe { mem::zeroed() };
+        let timeout = self.timeout.map_or(-1.0, |t| t.as_secs_f64());
+        unsafe { tibrvDispatcher_CreateEx(&mut ptr, self.dispatchable.dispatchable(), timeout) }
+            .map(|_| ())?;
+        // Created before naming, so the thread is stopped if that fails.
+        let dispatcher = Dispatcher {
+            inner: ptr,
+            dispatchable: self.dispatchable,
+        };
+        if let Some(name) = self.name {
+            unsafe { tibrvDispatcher_SetName(ptr, name.as_ptr()) }.map(|_| ())?;
+        }
+        Ok(dispatcher)
+    }
+}
+
+/// A background thread which continuously dispatches a `Queue` or
+/// `QueueGroup`.
+///
+/// Messages are moved off the Rendezvous queue as soon as they arrive,
+/// and can be consumed with [`Queue::recv`] from any thread. The thread
+/// is stopped when the `Dispatcher` is dropped.
+///
+/// ```no_run
+/// use tibrv::context::{RvCtx, Transport};
+/// use tibrv::event::{DispatcherBuilder, Queue};
+///
+/// let ctx = RvCtx::new().unwrap();
+/// let tp = Transport::intra_process(&ctx);
+///
+/// let queue = Queue::new(ctx.clone()).unwrap();
+/// let _sub = queue.subscribe(&tp, "PRICES.>").unwrap();
+/// let _dispatcher = DispatcherBuilder::new(queue.clone())
+///     .with_name("prices")
+///     .unwrap()
+///     .create()
+///     .unwrap();
+///
+/// let msg = queue.recv().unwrap();
+/// ```
+///
+/// [`Queue::recv`]: struct.Queue.html#method.recv
+pub struct Dispatcher<D: Dispatchable> {
+    inner: tibrvDispatcher,
+    dispatchable: D,
+}
+
+impl<D: Dispatchable> Dispatcher<D> {
+    /// Get the name of the dispatcher thread.
+    pub fn name(&self) -> Result<String, TibrvError> {
+        let mut ptr: *const ::std::os::raw::c_char = unsafe { mem::zeroed() };
+        unsafe {
+            tibrvDispatcher_GetName(self.inner, &mut ptr)
+                .map(|_| CStr::from_ptr(ptr).to_string_lossy().into_owned())
+        }
+    }
+
+    /// Get a reference to the queue or queue group being dispatched.
+    pub fn get_ref(&self) -> &D {
+        &self.dispatchable
+    }
+}
+
+impl<D: Dispatchable> Drop for Dispatcher<D> {
+    fn drop(&mut self) {
+        unsafe {
+            tibrvDispatcher_Destroy(self.inner);
         }
     }
 }
 
 /// Represents a subscription to a subject.
 ///
-/// Wraps the event, the event queue, and the `mpsc::Receiver`
-/// containing the `Msg` data.
+/// Wraps the listener event, and a handle to the event queue on which
+/// its messages are delivered. The listener is destroyed when this is
+/// dropped, along with any of its messages which haven't been read.
+///
+/// Reading from a `Subscription` only returns its own messages, even
+/// when its `Queue` is shared with other subscriptions. Read from the
+/// `Queue` itself to receive the messages of every subscription in order
+/// of arrival.
 pub struct Subscription {
     event: tibrvEvent,
     pub(crate) queue: Queue,
-    channel: mpsc::Receiver<Msg>,
 }
 
 impl Subscription {
-    // Blocking dispatch
-    fn dispatch(&self) -> Result<(), TibrvError> {
-        unsafe { tibrvQueue_TimedDispatch(self.queue.inner, -1.0) }.map(|_| ())
+    /// Get the next message available on this subscription.
+    ///
+    /// Dispatches the queue until a message for this subscription is
+    /// available. Messages for other subscriptions on a shared `Queue`
+    /// are left in its buffer.
+    pub fn next(&self) -> Result<Msg, TibrvError> {
+        self.queue.next_from(Some(self.event))
+    }
+
+    /// Get the next message available on this subscription, without
+    /// blocking.
+    pub fn try_next(&self) -> Result<Msg, mpsc::TryRecvError> {
+        self.queue.try_next_from(Some(self.event))
+    }
+
+    /// Wait for the next message on this subscription, without
+    /// dispatching its queue.
+    ///
+    /// See [`Queue::recv`](struct.Queue.html#method.recv).
+    pub fn recv(&self) -> Result<Msg, TibrvError> {
+        self.queue.recv_from(Some(self.event))
     }
 
-    // Non blocking try-dispatch.
-    fn poll(&self) -> Result<(), TibrvError> {
-        unsafe { tibrvQueue_TimedDispatch(self.queue.inner, 0.0) }.map(|_| ())
+    /// Get the queue on which this subscription's messages are delivered.
+    pub fn queue(&self) -> &Queue {
+        &self.queue
     }
 
+    /// Limit the number of events waiting in this subscription's
+    /// Rendezvous queue.
+    ///
+    /// See [`Queue::set_limit_policy`](struct.Queue.html#method.set_limit_policy).
+    pub fn set_limit_policy(&self, policy: LimitPolicy) -> Result<(), TibrvError> {
+        self.queue.set_limit_policy(policy)
+    }
+
+    /// Limit the number of dispatched messages waiting to be consumed.
+    ///
+    /// See [`Queue::set_buffer_limit`](struct.Queue.html#method.set_buffer_limit).
+    pub fn set_buffer_limit(&self, policy: LimitPolicy) -> Result<(), TibrvError> {
+        self.queue.set_buffer_limit(policy)
+    }
+
+    /// Get the number of messages discarded from the queue's buffer.
+    ///
+    /// See [`Queue::buffer_discarded`](struct.Queue.html#method.buffer_discarded).
+    pub fn buffer_discarded(&self) -> u64 {
+        self.queue.buffer_discarded()
+    }
+}
+
+/// An interface over subscriptions returned by an `RvTransport`.
+pub trait RvSubscription {
     /// Get the next message available on this subscription.
     ///
-    /// Blocks until a message is available in the queue.
-    pub fn next(&self) -> Result<Msg, TibrvError> {
-        if let Ok(m) = self.channel.try_recv() {
-            return Ok(m);
-        }
-        self.dispatch()?;
-        self.channel
-            .recv()
-            .context(ErrorKind::QueueError)
-            .map_err(TibrvError::from)
+    /// Blocks until a message is available.
+    fn next(&self) -> Result<Msg, TibrvError>;
+
+    /// Get the next message available on this subscription,
+    /// without blocking.
+    fn try_next(&self) -> Result<Msg, mpsc::TryRecvError>;
+}
+
+impl RvSubscription for Subscription {
+    fn next(&self) -> Result<Msg, TibrvError> {
+        Subscription::next(self)
     }
 
-    pub fn try_next(&self) -> Result<Msg, mpsc::TryRecvError> {
-        let _ = self.poll(); // Ignore this "error"
-        self.channel.try_recv()
+    fn try_next(&self) -> Result<Msg, mpsc::TryRecvError> {
+        Subscription::try_next(self)
     }
 }
 
@@ -144,13 +1023,166 @@ impl Drop for Subscription {
         unsafe {
             tibrvEvent_DestroyEx(self.event, None);
         }
+        // Nothing can read these now, and Rendezvous may reuse the handle.
+        self.queue.shared.buffer.lock().unwrap().remove(self.event);
+    }
+}
+
+/// Represents a repeating timer.
+///
+/// Wraps the timer event, a handle to the event queue, and the
+/// `mpsc::Receiver` which is signalled each time the timer fires.
+pub struct Timer {
+    event: tibrvEvent,
+    pub(crate) queue: Queue,
+    channel: mpsc::Receiver<()>,
+}
+
+impl Timer {
+    /// Create a timer which fires repeatedly, once every `interval`.
+    ///
+    /// The timer is dispatched on its own event queue, use `Queue::timer`
+    /// to share a queue with other events.
+    pub fn new(ctx: RvCtx, interval: Duration) -> Result<Self, TibrvError> {
+        Queue::new(ctx)?.timer(interval)
+    }
+
+    /// Wait for the next timer event.
+    ///
+    /// Blocks until the timer fires, returns immediately if the timer
+    /// has fired since the last call.
+    pub fn tick(&self) -> Result<(), TibrvError> {
+        dispatch_until(&self.queue, &self.channel)
+    }
+
+    /// Check whether the timer has fired, without blocking.
+    pub fn try_tick(&self) -> Result<(), mpsc::TryRecvError> {
+        let _ = self.queue.poll(); // Ignore this "error"
+        self.channel.try_recv()
+    }
+
+    /// Get the interval at which this timer fires.
+    pub fn interval(&self) -> Result<Duration, TibrvError> {
+        let mut interval: tibrv_f64 = 0.0;
+        unsafe { tibrvEvent_GetTimerInterval(self.event, &mut interval) }
+            .map(|_| Duration::from_secs_f64(interval))
+    }
+
+    /// Change the interval at which this timer fires.
+    ///
+    /// The next timer event will occur `interval` after this call.
+    pub fn set_interval(&mut self, interval: Duration) -> Result<(), TibrvError> {
+        unsafe { tibrvEvent_ResetTimerInterval(self.event, interval.as_secs_f64()) }.map(|_| ())
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        unsafe {
+            tibrvEvent_DestroyEx(self.event, Some(signal_complete));
+        }
+    }
+}
+
+/// The IO condition which triggers an `IoEvent`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum IoCondition {
+    /// The source has data available to read.
+    Read,
+    /// The source is able to accept data to be written.
+    Write,
+    /// An exceptional condition, such as out-of-band data, is pending.
+    Exception,
+}
+
+impl From<IoCondition> for tibrvIOType {
+    fn from(condition: IoCondition) -> Self {
+        match condition {
+            IoCondition::Read => TIBRV_IO_READ,
+            IoCondition::Write => TIBRV_IO_WRITE,
+            IoCondition::Exception => TIBRV_IO_EXCEPTION,
+        }
+    }
+}
+
+/// Represents an IO event, watching a file descriptor or socket.
+///
+/// Wraps the IO event, a handle to the event queue, and the
+/// `mpsc::Receiver` which is signalled each time the condition is met.
+pub struct IoEvent {
+    event: tibrvEvent,
+    pub(crate) queue: Queue,
+    channel: mpsc::Receiver<()>,
+}
+
+impl IoEvent {
+    /// Watch `source` for the given IO condition.
+    ///
+    /// On Unix `source` is a file descriptor, on Windows it must be
+    /// a socket. The event is dispatched on its own event queue, use
+    /// `Queue::io` to share a queue with other events.
+    pub fn new(ctx: RvCtx, source: i32, condition: IoCondition) -> Result<Self, TibrvError> {
+        Queue::new(ctx)?.io(source, condition)
+    }
+
+    /// Wait until the IO condition is met.
+    ///
+    /// Rendezvous will continue to signal the event for as long as the
+    /// condition holds, so data should be read or written before
+    /// waiting again.
+    pub fn wait(&self) -> Result<(), TibrvError> {
+        dispatch_until(&self.queue, &self.channel)
+    }
+
+    /// Check whether the IO condition has been met, without blocking.
+    pub fn try_wait(&self) -> Result<(), mpsc::TryRecvError> {
+        let _ = self.queue.poll(); // Ignore this "error"
+        self.channel.try_recv()
+    }
+
+    /// Get the IO condition this event is watching for.
+    pub fn condition(&self) -> Result<IoCondition, TibrvError> {
+        let mut io_type: tibrvIOType = TIBRV_IO_READ;
+        unsafe { tibrvEvent_GetIOType(self.event, &mut io_type) }.and_then(|_| match io_type {
+            TIBRV_IO_READ => Ok(IoCondition::Read),
+            TIBRV_IO_WRITE => Ok(IoCondition::Write),
+            TIBRV_IO_EXCEPTION => Ok(IoCondition::Exception),
+            _ => Err(ErrorKind::from(TIBRV_INVALID_IO_CONDITION).into()),
+        })
+    }
+
+    /// Get the file descriptor or socket this event is watching.
+    pub fn source(&self) -> Result<i32, TibrvError> {
+        let mut source: tibrv_i32 = 0;
+        unsafe { tibrvEvent_GetIOSource(self.event, &mut source) }.map(|_| source as i32)
+    }
+}
+
+impl Drop for IoEvent {
+    fn drop(&mut self) {
+        unsafe {
+            tibrvEvent_DestroyEx(self.event, Some(signal_complete));
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use context::{RvCtx, TransportBuilder};
-    use event::Queue;
+    use crate::context::{RvCtx, Transport};
+    use crate::errors::ErrorKind;
+    use crate::event::{
+        DispatcherBuilder, IoCondition, IoEvent, LimitPolicy, Queue, QueueGroup, Timer,
+    };
+    use crate::message::{BorrowedMsg, Msg};
+    use std::panic;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    fn send(tp: &Transport, subject: &str) {
+        let mut msg = Msg::new().unwrap();
+        msg.set_send_subject(subject).unwrap();
+        tp.send(&mut msg).unwrap();
+    }
 
     #[test]
     fn creation() {
@@ -160,14 +1192,367 @@ mod tests {
         assert_eq!(0, queue.unwrap().count().unwrap());
     }
 
-    #[ignore]
-    // Requires a running rvd
     #[test]
     fn subscribe() {
         let ctx = RvCtx::new().unwrap();
         let queue = Queue::new(ctx.clone()).unwrap();
-        let tp = TransportBuilder::new(ctx.clone()).create().unwrap();
+        let tp = Transport::intra_process(&ctx);
         let sub = queue.subscribe(&tp, "TEST");
         assert!(sub.is_ok());
     }
+
+    #[test]
+    fn timer_interval() {
+        let ctx = RvCtx::new().unwrap();
+        let mut timer = Timer::new(ctx, Duration::from_millis(500)).unwrap();
+        assert_eq!(Duration::from_millis(500), timer.interval().unwrap());
+
+        timer.set_interval(Duration::from_secs(2)).unwrap();
+        assert_eq!(Duration::from_secs(2), timer.interval().unwrap());
+    }
+
+    #[test]
+    fn timer_tick() {
+        let ctx = RvCtx::new().unwrap();
+        let timer = Timer::new(ctx, Duration::from_millis(10)).unwrap();
+        assert!(timer.tick().is_ok());
+        assert!(timer.tick().is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn io_event() {
+        use std::io::Write;
+        use std::os::unix::io::AsRawFd;
+        use std::os::unix::net::UnixStream;
+
+        let ctx = RvCtx::new().unwrap();
+        let (mut tx, rx) = UnixStream::pair().unwrap();
+
+        let event = IoEvent::new(ctx, rx.as_raw_fd(), IoCondition::Read).unwrap();
+        assert_eq!(IoCondition::Read, event.condition().unwrap());
+        assert_eq!(rx.as_raw_fd(), event.source().unwrap());
+        assert!(event.try_wait().is_err());
+
+        tx.write_all(b"ready").unwrap();
+        assert!(event.wait().is_ok());
+    }
+
+    #[test]
+    fn dispatch_empty() {
+        let ctx = RvCtx::new().unwrap();
+        let queue = Queue::new(ctx).unwrap();
+        assert!(!queue.poll().unwrap());
+        let res = queue.timed_dispatch(Duration::from_millis(10));
+        let _ = res.map_err(|e| assert_eq!(ErrorKind::Timeout, e.kind()));
+    }
+
+    #[test]
+    fn shared_queue_order() {
+        let ctx = RvCtx::new().unwrap();
+        let tp = Transport::intra_process(&ctx);
+        let queue = Queue::new(ctx.clone()).unwrap();
+        let _a = queue.subscribe(&tp, "TEST.A").unwrap();
+        let _b = queue.subscribe(&tp, "TEST.B").unwrap();
+
+        send(&tp, "TEST.A");
+        send(&tp, "TEST.B");
+        send(&tp, "TEST.A");
+
+        let subjects = (0..3)
+            .map(|_| queue.next().unwrap().get_send_subject().unwrap().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(subjects, vec!["TEST.A", "TEST.B", "TEST.A"]);
+    }
+
+    #[test]
+    fn shared_queue_subscription_reads() {
+        let ctx = RvCtx::new().unwrap();
+        let tp = Transport::intra_process(&ctx);
+        let queue = Queue::new(ctx.clone()).unwrap();
+        let a = queue.subscribe(&tp, "TEST.A").unwrap();
+        let b = queue.subscribe(&tp, "TEST.B").unwrap();
+
+        send(&tp, "TEST.A");
+        send(&tp, "TEST.B");
+        send(&tp, "TEST.A");
+
+        // Each subscription skips over the other's messages.
+        let msg = b.next().unwrap();
+        assert_eq!("TEST.B", msg.get_send_subject().unwrap().unwrap());
+        assert!(b.try_next().is_err());
+        for _ in 0..2 {
+            let msg = a.try_next().unwrap();
+            assert_eq!("TEST.A", msg.get_send_subject().unwrap().unwrap());
+        }
+        assert!(queue.try_recv().is_err());
+
+        // Unread messages go with a dropped subscription.
+        send(&tp, "TEST.B");
+        send(&tp, "TEST.A");
+        while queue.poll().unwrap() {}
+        drop(a);
+        let msg = queue.try_recv().unwrap();
+        assert_eq!("TEST.B", msg.get_send_subject().unwrap().unwrap());
+        assert!(queue.try_recv().is_err());
+    }
+
+    #[test]
+    fn unsubscribe_on_drop() {
+        let ctx = RvCtx::new().unwrap();
+        let tp = Transport::intra_process(&ctx);
+        let queue = Queue::new(ctx.clone()).unwrap();
+        let kept = queue.subscribe(&tp, "TEST.KEPT").unwrap();
+        drop(queue.subscribe(&tp, "TEST.DROPPED").unwrap());
+
+        send(&tp, "TEST.DROPPED");
+        send(&tp, "TEST.KEPT");
+
+        let msg = kept.next().unwrap();
+        assert_eq!("TEST.KEPT", msg.get_send_subject().unwrap().unwrap());
+        assert_eq!(0, queue.count().unwrap());
+    }
+
+    #[test]
+    fn timer_shares_queue() {
+        let ctx = RvCtx::new().unwrap();
+        let tp = Transport::intra_process(&ctx);
+        let queue = Queue::new(ctx.clone()).unwrap();
+        let sub = queue.subscribe(&tp, "TEST.TIMER").unwrap();
+        let timer = queue.timer(Duration::from_millis(10)).unwrap();
+
+        send(&tp, "TEST.TIMER");
+        assert!(timer.tick().is_ok());
+        assert!(sub.next().is_ok());
+    }
+
+    #[test]
+    fn priority() {
+        let ctx = RvCtx::new().unwrap();
+        let queue = Queue::new(ctx).unwrap();
+        assert_eq!(1, queue.priority().unwrap());
+        queue.set_priority(5).unwrap();
+        assert_eq!(5, queue.priority().unwrap());
+    }
+
+    #[test]
+    fn queue_group_priority() {
+        let ctx = RvCtx::new().unwrap();
+        let tp = Transport::intra_process(&ctx);
+
+        let bulk = Queue::new(ctx.clone()).unwrap();
+        let control = Queue::new(ctx.clone()).unwrap();
+        control.set_priority(10).unwrap();
+        let _bulk_sub = bulk.subscribe(&tp, "TEST.BULK").unwrap();
+        let _control_sub = control.subscribe(&tp, "TEST.CONTROL").unwrap();
+
+        let mut group = QueueGroup::new(ctx.clone()).unwrap();
+        group.add(&bulk).unwrap();
+        group.add(&control).unwrap();
+
+        send(&tp, "TEST.BULK");
+        send(&tp, "TEST.CONTROL");
+
+        // The control message is dispatched first, despite arriving later.
+        group.dispatch().unwrap();
+        assert!(control.try_recv().is_ok());
+        assert!(bulk.try_recv().is_err());
+
+        group.dispatch().unwrap();
+        assert!(bulk.try_recv().is_ok());
+        assert!(!group.poll().unwrap());
+
+        group.remove(&bulk).unwrap();
+        send(&tp, "TEST.BULK");
+        let res = group.timed_dispatch(Duration::from_millis(10));
+        let _ = res.map_err(|e| assert_eq!(ErrorKind::Timeout, e.kind()));
+    }
+
+    #[test]
+    fn limit_policy() {
+        let ctx = RvCtx::new().unwrap();
+        let queue = Queue::new(ctx).unwrap();
+        assert_eq!(LimitPolicy::DiscardNone, queue.limit_policy().unwrap());
+
+        let policy = LimitPolicy::DiscardFirst {
+            max_events: 100,
+            discard_amount: 10,
+        };
+        queue.set_limit_policy(policy).unwrap();
+        assert_eq!(policy, queue.limit_policy().unwrap());
+    }
+
+    #[test]
+    fn invalid_buffer_limit() {
+        let ctx = RvCtx::new().unwrap();
+        let queue = Queue::new(ctx).unwrap();
+        let policy = LimitPolicy::DiscardNew {
+            max_events: 0,
+            discard_amount: 1,
+        };
+        assert!(queue.set_buffer_limit(policy).is_err());
+        assert_eq!(LimitPolicy::DiscardNone, queue.buffer_limit());
+    }
+
+    // Fill a buffer limited to two messages with four, returning the
+    // subjects which remain.
+    fn overflow(policy: LimitPolicy) -> (Vec<String>, u64) {
+        let ctx = RvCtx::new().unwrap();
+        let tp = Transport::intra_process(&ctx);
+        let queue = Queue::new(ctx.clone()).unwrap();
+        let _sub = queue.subscribe(&tp, "TEST.LIMIT.*").unwrap();
+        queue.set_buffer_limit(policy).unwrap();
+
+        for i in 0..4 {
+            send(&tp, &format!("TEST.LIMIT.{}", i));
+        }
+        while queue.poll().unwrap() {}
+
+        let mut subjects = vec![];
+        while let Ok(msg) = queue.try_recv() {
+            subjects.push(msg.get_send_subject().unwrap().unwrap());
+        }
+        (subjects, queue.buffer_discarded())
+    }
+
+    #[test]
+    fn buffer_discard_new() {
+        let (subjects, discarded) = overflow(LimitPolicy::DiscardNew {
+            max_events: 2,
+            discard_amount: 1,
+        });
+        assert_eq!(subjects, vec!["TEST.LIMIT.0", "TEST.LIMIT.1"]);
+        assert_eq!(2, discarded);
+    }
+
+    #[test]
+    fn buffer_discard_first() {
+        let (subjects, discarded) = overflow(LimitPolicy::DiscardFirst {
+            max_events: 2,
+            discard_amount: 1,
+        });
+        assert_eq!(subjects, vec!["TEST.LIMIT.2", "TEST.LIMIT.3"]);
+        assert_eq!(2, discarded);
+    }
+
+    #[test]
+    fn buffer_discard_last() {
+        let (subjects, discarded) = overflow(LimitPolicy::DiscardLast {
+            max_events: 2,
+            discard_amount: 1,
+        });
+        assert_eq!(subjects, vec!["TEST.LIMIT.0", "TEST.LIMIT.3"]);
+        assert_eq!(2, discarded);
+    }
+
+    #[test]
+    fn dispatcher() {
+        let ctx = RvCtx::new().unwrap();
+        let tp = Transport::intra_process(&ctx);
+        let queue = Queue::new(ctx.clone()).unwrap();
+        let _sub = queue.subscribe(&tp, "TEST.DISPATCHER").unwrap();
+        let dispatcher = DispatcherBuilder::new(queue.clone())
+            .with_name("test-dispatcher")
+            .unwrap()
+            .create()
+            .unwrap();
+        assert_eq!("test-dispatcher", dispatcher.name().unwrap());
+
+        send(&tp, "TEST.DISPATCHER");
+        let msg = queue.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(
+            Some("TEST.DISPATCHER".to_string()),
+            msg.get_send_subject().unwrap()
+        );
+    }
+
+    #[test]
+    fn dispatcher_queue_group() {
+        let ctx = RvCtx::new().unwrap();
+        let tp = Transport::intra_process(&ctx);
+        let queue = Queue::new(ctx.clone()).unwrap();
+        let _sub = queue.subscribe(&tp, "TEST.DISPATCHER.GROUP").unwrap();
+        let mut group = QueueGroup::new(ctx.clone()).unwrap();
+        group.add(&queue).unwrap();
+        let _dispatcher = DispatcherBuilder::new(group).create().unwrap();
+
+        send(&tp, "TEST.DISPATCHER.GROUP");
+        assert!(queue.recv_timeout(Duration::from_secs(5)).is_ok());
+    }
+
+    #[test]
+    fn recv_timeout() {
+        let ctx = RvCtx::new().unwrap();
+        let queue = Queue::new(ctx).unwrap();
+        match queue.recv_timeout(Duration::from_millis(10)) {
+            Err(e) => assert_eq!(ErrorKind::Timeout, e.kind()),
+            Ok(_) => panic!("Expected a timeout"),
+        }
+    }
+
+    #[test]
+    fn listen() {
+        let ctx = RvCtx::new().unwrap();
+        let tp = Transport::intra_process(&ctx);
+        let queue = Queue::new(ctx.clone()).unwrap();
+
+        let subjects = Arc::new(Mutex::new(vec![]));
+        let seen = subjects.clone();
+        let _listener = queue
+            .listen(&tp, "TEST.LISTEN.*", move |msg: &BorrowedMsg| {
+                seen.lock()
+                    .unwrap()
+                    .push(msg.get_send_subject().unwrap().unwrap());
+            })
+            .unwrap();
+
+        send(&tp, "TEST.LISTEN.A");
+        send(&tp, "TEST.LISTEN.B");
+        while queue.poll().unwrap() {}
+
+        assert_eq!(
+            vec!["TEST.LISTEN.A", "TEST.LISTEN.B"],
+            *subjects.lock().unwrap()
+        );
+        // Nothing is copied into the queue's buffer.
+        assert!(queue.try_recv().is_err());
+    }
+
+    #[test]
+    fn listener_drops_closure() {
+        let ctx = RvCtx::new().unwrap();
+        let tp = Transport::intra_process(&ctx);
+        let queue = Queue::new(ctx.clone()).unwrap();
+
+        let token = Arc::new(());
+        let held = token.clone();
+        let listener = queue
+            .listen(&tp, "TEST.LISTEN.DROP", move |_: &BorrowedMsg| {
+                let _ = &held;
+            })
+            .unwrap();
+        assert_eq!(2, Arc::strong_count(&token));
+        drop(listener);
+        assert_eq!(1, Arc::strong_count(&token));
+    }
+
+    #[test]
+    fn listener_panic() {
+        let ctx = RvCtx::new().unwrap();
+        let tp = Transport::intra_process(&ctx);
+        let queue = Queue::new(ctx.clone()).unwrap();
+        let _listener = queue
+            .listen(&tp, "TEST.LISTEN.PANIC", |_: &BorrowedMsg| {
+                panic!("listener panicked")
+            })
+            .unwrap();
+
+        send(&tp, "TEST.LISTEN.PANIC");
+        let result = panic::catch_unwind(|| queue.dispatch());
+        let payload = result.unwrap_err();
+        assert_eq!(Some(&"listener panicked"), payload.downcast_ref::<&str>());
+
+        // The queue remains usable once the panic has been resumed.
+        assert!(!queue.poll().unwrap());
+    }
 }
@@ -6,9 +6,9 @@
 
 #![allow(clippy::float_cmp)]
 
+use crate::errors::*;
+use crate::message::{BorrowedMsg, Msg};
 use chrono::NaiveDateTime;
-use errors::*;
-use message::{BorrowedMsg, Msg};
 use std;
 use std::ffi::{CStr, CString};
 use std::marker::PhantomData;
@@ -457,9 +457,7 @@ pub unsafe fn tibrv_encode_opaque<'a, T: Copy>(
 /// is lost (internally the slice is passed as `void*`).
 /// Therefore this function is approximately as unsafe as `std::mem::transmute`,
 /// except without the soft and fluffy blanket of size checking.
-pub unsafe fn tibrv_try_decode_opaque<T: Copy>(
-    msg: &MsgField,
-) -> Result<&[T], TibrvError> {
+pub unsafe fn tibrv_try_decode_opaque<T: Copy>(msg: &MsgField) -> Result<&[T], TibrvError> {
     if msg.inner.type_ != TIBRVMSG_OPAQUE as u8 {
         Err(ErrorKind::FieldTypeError)?
     } else {
@@ -8,7 +8,7 @@
 //! [1]: https://www.tibco.com/products/tibco-rendezvous
 //!
 //! The tibrv library attempts to make using libtibrv as ergonomic as possible,
-//! and includes an optional, Tokio-based asynchronous layer.
+//! and includes an optional asynchronous layer, which works with any executor.
 //!
 //! ## Environment Setup
 //!
@@ -37,6 +37,10 @@
 //! features = ["tibrv_8_3"]
 //! ```
 //!
+//! The `async` feature enables the `std::future` based interfaces in the
+//! `async` module, and the `tokio` feature adds helpers for spawning them
+//! onto a Tokio runtime.
+//!
 //! ## Working with Messages
 //!
 //! A message is the main structure used to encapsulate data sent or received
@@ -110,34 +114,38 @@ extern crate chrono;
 extern crate failure;
 extern crate failure_derive;
 extern crate tibrv_sys;
+extern crate tibrv_wire;
 
-#[cfg(feature = "tokio")]
+#[cfg(feature = "async")]
 extern crate futures;
 #[cfg(feature = "tokio")]
-extern crate mio;
-#[cfg(feature = "tokio")]
 extern crate tokio;
 
 #[macro_use]
 pub mod errors;
 
-#[cfg(feature = "tokio")]
-pub mod async;
+#[cfg(feature = "async")]
+pub mod r#async;
 pub mod context;
 pub mod event;
 pub mod field;
 pub mod message;
+pub mod mock;
+mod text;
+pub mod wire;
+
+#[cfg(test)]
+mod test_util;
 
 #[cfg(test)]
 mod tests {
-    // Both the send and recv tests require a running RVD on the
-    // default port on localhost.
+    // Both the send and recv tests use the intra-process transport,
+    // so don't require a running RVD.
     #[test]
-    #[ignore]
     fn send_msg() {
-        use context::{RvCtx, TransportBuilder};
-        use field::Builder;
-        use message::Msg;
+        use crate::context::{RvCtx, Transport};
+        use crate::field::Builder;
+        use crate::message::Msg;
         use std::ffi::CString;
 
         let ctx = RvCtx::new().unwrap();
@@ -150,29 +158,32 @@ mod tests {
 
         assert!(msg.set_send_subject("TEST").is_ok());
 
-        let tp = TransportBuilder::new(ctx.clone()).create().unwrap(); // Create default transport
+        let tp = Transport::intra_process(&ctx);
 
         assert!(tp.send(&mut msg).is_ok());
     }
 
     #[test]
-    #[ignore]
     fn recv_msg() {
-        use event::Queue;
-        use context::{RvCtx, TransportBuilder};
-        use field::Decodable;
+        use crate::context::{RvCtx, Transport};
+        use crate::event::Queue;
+        use crate::field::Decodable;
         use std::ffi::CStr;
 
         let ctx = RvCtx::new().expect("Couldn't create RV machinery");
 
-        let tp = TransportBuilder::new(ctx.clone()).create().expect("Couldn't create transport");
+        let tp = Transport::intra_process(&ctx);
         let q = Queue::new(ctx).expect("Couldn't create queue");
-        let sub = q.subscribe(&tp, "TEST").expect("Couldn't register subscription");
+        let sub = q
+            .subscribe(&tp, "TEST")
+            .expect("Couldn't register subscription");
 
         send_msg();
 
         let msg = sub.next().expect("Couldn't get next message.");
-        let field = msg.get_field_by_name("DATA").expect("Couldn't find DATA Field");
+        let field = msg
+            .get_field_by_name("DATA")
+            .expect("Couldn't find DATA Field");
         let data = <&CStr>::tibrv_try_decode(&field);
         assert!(data.is_ok());
     }
@@ -1,13 +1,17 @@
 //! Interfaces for creating and using Rendezvous Messages
 
-use errors::*;
+use crate::errors::*;
+use crate::field::*;
+use crate::text;
+use crate::wire;
 use failure::ResultExt;
-use field::*;
 use std;
 use std::ffi::{CStr, CString};
+use std::fmt;
 use std::marker::PhantomData;
 use std::mem;
-use std::os::raw::c_char;
+use std::ops::Deref;
+use std::os::raw::{c_char, c_void};
 use tibrv_sys::*;
 
 pub struct MsgIter<'a> {
@@ -51,6 +55,7 @@ impl<'a> IntoIterator for &'a Msg {
 /// of the Rust application. When this type is dropped,
 /// `tibrvMsg_Destroy` will be run to free any memory allocated
 /// to store the message.
+#[repr(transparent)]
 pub struct Msg {
     pub(crate) inner: tibrvMsg,
 }
@@ -82,16 +87,60 @@ impl Msg {
         unsafe { tibrvMsg_AddField(self.inner, &mut field.inner) }.map(|_| self)
     }
 
+    /// Update a field in this message, in place.
+    ///
+    /// The existing field is found by the id of `field` if it has one,
+    /// otherwise by name, and keeps its position in the message. If there
+    /// is no such field then `field` is added, as with `add_field`.
+    ///
+    /// Fails if the existing field has a different type.
+    pub fn update_field(&mut self, field: &mut MsgField) -> Result<&mut Self, TibrvError> {
+        unsafe { tibrvMsg_UpdateField(self.inner, &mut field.inner) }.map(|_| self)
+    }
+
+    /// Update a field in this message, in place.
+    ///
+    /// This variant finds the field by name.
+    pub fn update_field_by_name<T: Encodable>(
+        &mut self,
+        name: &str,
+        value: &T,
+    ) -> Result<&mut Self, TibrvError> {
+        self.update_field(&mut value.tibrv_encode(Some(name), None))
+    }
+
+    /// Update a field in this message, in place.
+    ///
+    /// This variant finds the field by id, and keeps its name. A field
+    /// with an id must also have a name, so unlike `update_field_by_name`
+    /// a missing field is not added, this fails with `TIBRV_NOT_FOUND`
+    /// instead. Fails with `TIBRV_INVALID_ARG` if `id` is larger than a
+    /// field id can be.
+    pub fn update_field_by_id<T: Encodable>(
+        &mut self,
+        id: u32,
+        value: &T,
+    ) -> Result<&mut Self, TibrvError> {
+        if id > u32::from(u16::MAX) {
+            Err(ErrorKind::from(TIBRV_INVALID_ARG))?
+        }
+        let name = {
+            let field = self.get_field_by_id(id)?;
+            unsafe { CStr::from_ptr(field.inner.inner.name) }
+                .to_str()
+                .context(ErrorKind::StrContentError)?
+                .to_owned()
+        };
+        self.update_field(&mut value.tibrv_encode(Some(&name), Some(id)))
+    }
+
     /// Get a specified field from this message.
     ///
     /// Data in scalar fields is copied, and data in pointer fields
     /// is guaranteed to live at least as long as the parent `Msg`.
     ///
     /// This variant retrieves the field by name.
-    pub fn get_field_by_name<'a>(
-        &'a self,
-        name: &str,
-    ) -> Result<BorrowedMsgField<'a>, TibrvError> {
+    pub fn get_field_by_name<'a>(&'a self, name: &str) -> Result<BorrowedMsgField<'a>, TibrvError> {
         self.get_field(Some(name), None)
     }
 
@@ -111,10 +160,7 @@ impl Msg {
     /// is guaranteed to live at least as long as the parent `Msg`.
     ///
     /// This variant retrieves the field by index.
-    pub fn get_field_by_index(
-        &self,
-        index: u32,
-    ) -> Result<BorrowedMsgField, TibrvError> {
+    pub fn get_field_by_index(&self, index: u32) -> Result<BorrowedMsgField, TibrvError> {
         let mut field: tibrvMsgField = unsafe { mem::zeroed() };
 
         unsafe { tibrvMsg_GetFieldByIndex(self.inner, &mut field, index as tibrv_u32) }
@@ -124,9 +170,10 @@ impl Msg {
                         unsafe { CStr::from_ptr(field.name) }
                             .to_string_lossy()
                             .into_owned(),
-                    ).context(ErrorKind::StrContentError)
-                        .map_err(TibrvError::from)
-                        .map(Some)
+                    )
+                    .context(ErrorKind::StrContentError)
+                    .map_err(TibrvError::from)
+                    .map(Some)
                 } else {
                     Ok(None)
                 }
@@ -163,7 +210,8 @@ impl Msg {
                 &mut field,
                 id.unwrap_or(0) as tibrv_u16,
             )
-        }.map(|_| BorrowedMsgField {
+        }
+        .map(|_| BorrowedMsgField {
             inner: MsgField {
                 name: field_name,
                 inner: field,
@@ -178,7 +226,7 @@ impl Msg {
     /// is guaranteed to live at least as long as the parent `Msg`.
     ///
     /// This variant retrieves the field by name.
-    pub fn remove_field_by_name(&self, name: &str) -> Result<(), TibrvError> {
+    pub fn remove_field_by_name(&mut self, name: &str) -> Result<(), TibrvError> {
         self.remove_field(Some(name), None)
     }
 
@@ -188,15 +236,11 @@ impl Msg {
     /// is guaranteed to live at least as long as the parent `Msg`.
     ///
     /// This variant retrieves the field by id.
-    pub fn remove_field_by_id(&self, id: u32) -> Result<(), TibrvError> {
+    pub fn remove_field_by_id(&mut self, id: u32) -> Result<(), TibrvError> {
         self.remove_field(None, Some(id))
     }
 
-    fn remove_field(
-        &self,
-        name: Option<&str>,
-        id: Option<u32>,
-    ) -> Result<(), TibrvError> {
+    fn remove_field(&mut self, name: Option<&str>, id: Option<u32>) -> Result<(), TibrvError> {
         assert_ne!(
             name.is_some(),
             id.is_some(),
@@ -207,8 +251,7 @@ impl Msg {
             .map_or(Ok(None), |n| n.map(Some))?;
 
         let name_ptr = field_name.as_ref().map_or(std::ptr::null(), |m| m.as_ptr());
-        unsafe { tibrvMsg_RemoveFieldEx(self.inner, name_ptr, id.unwrap_or(0) as u16) }
-            .map(|_| ())
+        unsafe { tibrvMsg_RemoveFieldEx(self.inner, name_ptr, id.unwrap_or(0) as u16) }.map(|_| ())
     }
 
     /// Get the number of fields within this message.
@@ -235,6 +278,50 @@ impl Msg {
         unsafe { tibrvMsg_GetByteSize(self.inner, &mut ptr) }.map(|_| ptr as u32)
     }
 
+    /// Copy the message into the Rendezvous wire format.
+    ///
+    /// The bytes are self-describing, so can be stored or passed over
+    /// another channel and turned back into an identical message with
+    /// `from_bytes`. The send and reply subjects are not included.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TibrvError> {
+        let size = self.byte_size()?;
+        let mut bytes = vec![0u8; size as usize];
+        unsafe {
+            tibrvMsg_GetAsBytesCopy(
+                self.inner,
+                bytes.as_mut_ptr() as *mut c_void,
+                size as tibrv_u32,
+            )
+        }
+        .map(|_| bytes)
+    }
+
+    /// Borrow the message in the Rendezvous wire format, without copying.
+    ///
+    /// The bytes belong to the message, see `to_bytes` for an owned copy.
+    pub fn as_bytes(&self) -> Result<&[u8], TibrvError> {
+        let size = self.byte_size()?;
+        let mut ptr: *const c_void = std::ptr::null();
+        unsafe { tibrvMsg_GetAsBytes(self.inner, &mut ptr) }
+            .map(|_| unsafe { std::slice::from_raw_parts(ptr as *const u8, size as usize) })
+    }
+
+    /// Construct a message from bytes in the Rendezvous wire format,
+    /// as produced by `to_bytes` or `as_bytes`.
+    ///
+    /// The bytes are copied into the new message. Fails with
+    /// `TIBRV_CORRUPT_MSG` if the header is invalid or `bytes` is shorter
+    /// than the size it records.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TibrvError> {
+        // Rendezvous reads as many bytes as the header says are there,
+        // so make sure they are before handing the buffer over.
+        wire::message_size(bytes)?;
+
+        let mut ptr: tibrvMsg = unsafe { mem::zeroed() };
+        unsafe { tibrvMsg_CreateFromBytes(&mut ptr, bytes.as_ptr() as *const c_void) }
+            .map(|_| Msg { inner: ptr })
+    }
+
     /// Gets the send subject for the message.
     ///
     /// Will return `Ok(None)` if the send subject is empty.
@@ -282,6 +369,96 @@ impl Msg {
         let subject_c = CString::new(subject).context(ErrorKind::StrContentError)?;
         unsafe { tibrvMsg_SetReplySubject(self.inner, subject_c.as_ptr()) }.map(|_| ())
     }
+
+    /// Convert the fields of this message to text.
+    ///
+    /// Uses the Rendezvous text format, including nested messages and
+    /// the contents of arrays, for example `{SYMBOL="VOD.L" BID=101.5}`.
+    /// The send and reply subjects are not included.
+    pub fn to_text(&self) -> Result<String, TibrvError> {
+        let mut ptr: *const c_char = unsafe { mem::zeroed() };
+        unsafe { tibrvMsg_ConvertToString(self.inner, &mut ptr) }.map(|_| {
+            unsafe { CStr::from_ptr(ptr) }
+                .to_string_lossy()
+                .into_owned()
+        })
+    }
+
+    /// Construct a message from the Rendezvous text format.
+    ///
+    /// This is the reverse of `to_text`, accepting strings, nested
+    /// messages, numeric arrays, booleans, IPv4 addresses and datetimes
+    /// such as `2019-03-05 12:00:00.000000000Z`. Fields and array elements
+    /// may be separated by whitespace or commas.
+    ///
+    /// The text doesn't record the width of numbers, so integers become
+    /// `i32` fields, or `i64` or `u64` if they don't fit, and decimals
+    /// become `f64`. A Rust style suffix such as `100u16` or `1.5f32` sets
+    /// the type explicitly, and `7500port` is an IP port. Array elements
+    /// all share a type, taken from any suffixed element.
+    ///
+    /// Invalid text fails with `ErrorKind::ParseError`, giving the line and
+    /// column of the problem.
+    ///
+    /// ```no_run
+    /// use tibrv::message::Msg;
+    ///
+    /// let msg = Msg::from_text(r#"{SYMBOL="VOD.L" BID=101.5 LEGS={QTY=[100u32 200]}}"#).unwrap();
+    /// assert_eq!(3, msg.num_fields().unwrap());
+    /// ```
+    pub fn from_text(text: &str) -> Result<Self, TibrvError> {
+        text::parse(text)
+    }
+
+    fn debug_fmt(&self, name: &str, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = self.to_text().unwrap_or_else(|e| format!("<{}>", e));
+        f.debug_struct(name)
+            .field("send_subject", &self.get_send_subject().unwrap_or(None))
+            .field("reply_subject", &self.get_reply_subject().unwrap_or(None))
+            .field("fields", &Truncated(&text))
+            .finish()
+    }
+}
+
+impl fmt::Display for Msg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.to_text() {
+            Ok(text) => f.write_str(&text),
+            Err(e) => write!(f, "<{}>", e),
+        }
+    }
+}
+
+/// Includes the send and reply subjects, and the text of the fields,
+/// truncated if the message is very large.
+impl fmt::Debug for Msg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.debug_fmt("Msg", f)
+    }
+}
+
+// The most bytes of message text included in `Debug` output.
+const DEBUG_TEXT_LIMIT: usize = 4096;
+
+// Writes message text unquoted, truncated to `DEBUG_TEXT_LIMIT`.
+struct Truncated<'a>(&'a str);
+
+impl<'a> fmt::Debug for Truncated<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0.len() <= DEBUG_TEXT_LIMIT {
+            return f.write_str(self.0);
+        }
+        let mut end = DEBUG_TEXT_LIMIT;
+        while !self.0.is_char_boundary(end) {
+            end -= 1;
+        }
+        write!(
+            f,
+            "{}... ({} bytes truncated)",
+            &self.0[..end],
+            self.0.len() - end
+        )
+    }
 }
 
 // Ensure we clean up messages we're responsible for.
@@ -300,10 +477,36 @@ impl Drop for Msg {
 /// The memory referenced by this type of Message is assumed to be
 /// the responsibility of the Rendezvous C library, and will not be
 /// freed when the `BorrowedMsg` is dropped.
+///
+/// All of the read-only methods of `Msg` are available on a
+/// `BorrowedMsg` through `Deref`.
+#[repr(transparent)]
 pub struct BorrowedMsg {
     pub(crate) inner: tibrvMsg,
 }
 
+impl Deref for BorrowedMsg {
+    type Target = Msg;
+
+    fn deref(&self) -> &Msg {
+        // Both types wrap a single `tibrvMsg`, and a `Msg` reached
+        // through a reference is never dropped.
+        unsafe { &*(self as *const BorrowedMsg as *const Msg) }
+    }
+}
+
+impl fmt::Display for BorrowedMsg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl fmt::Debug for BorrowedMsg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.debug_fmt("BorrowedMsg", f)
+    }
+}
+
 impl BorrowedMsg {
     /// Transform a BorrowedMsg into an owned Msg.
     ///
@@ -376,11 +579,10 @@ mod tests {
             .encode();
 
         let mut msg = Msg::new().unwrap();
-        assert!(
-            msg.add_field(&mut field)
-                .and_then(|m| m.add_field(&mut field2))
-                .is_ok()
-        );
+        assert!(msg
+            .add_field(&mut field)
+            .and_then(|m| m.add_field(&mut field2))
+            .is_ok());
 
         assert_eq!(2, msg.num_fields().unwrap());
 
@@ -422,11 +624,10 @@ mod tests {
             .encode();
 
         let mut msg = Msg::new().unwrap();
-        assert!(
-            msg.add_field(&mut field)
-                .and_then(|m| m.add_field(&mut field2))
-                .is_ok()
-        );
+        assert!(msg
+            .add_field(&mut field)
+            .and_then(|m| m.add_field(&mut field2))
+            .is_ok());
 
         assert_eq!(2, msg.num_fields().unwrap());
 
@@ -456,12 +657,11 @@ mod tests {
             .encode();
 
         let mut msg = Msg::new().unwrap();
-        assert!(
-            msg.add_field(&mut field)
-                .unwrap()
-                .add_field(&mut field2)
-                .is_ok()
-        );
+        assert!(msg
+            .add_field(&mut field)
+            .unwrap()
+            .add_field(&mut field2)
+            .is_ok());
 
         assert_eq!(2, msg.num_fields().unwrap());
 
@@ -472,6 +672,69 @@ mod tests {
         assert_eq!(0, msg.num_fields().unwrap());
     }
 
+    #[test]
+    fn update_fields() {
+        let mut msg = Msg::new().unwrap();
+        for (name, id, value) in &[("BID", 1, 100.5), ("ASK", 2, 101.0), ("LAST", 3, 100.75)] {
+            let mut field = Builder::new(value).with_name(name).with_id(*id).encode();
+            msg.add_field(&mut field).unwrap();
+        }
+
+        let mut field = Builder::new(&100.25).with_name("BID").with_id(1).encode();
+        msg.update_field(&mut field).unwrap();
+        msg.update_field_by_name("ASK", &101.25).unwrap();
+        msg.update_field_by_id(3, &100.5).unwrap();
+
+        // Updated in place, so the order is unchanged.
+        assert_eq!(3, msg.num_fields().unwrap());
+        let fields: Vec<(String, f64)> = msg
+            .into_iter()
+            .map(|f| {
+                let f = f.unwrap();
+                let name = f.name.as_ref().unwrap().to_str().unwrap().to_owned();
+                (name, f.try_decode::<f64>().unwrap())
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                ("BID".to_owned(), 100.25),
+                ("ASK".to_owned(), 101.25),
+                ("LAST".to_owned(), 100.5)
+            ],
+            fields
+        );
+
+        // Missing fields are added.
+        msg.update_field_by_name("VOLUME", &1000u32).unwrap();
+        assert_eq!(4, msg.num_fields().unwrap());
+        let volume = msg.get_field_by_index(3).unwrap();
+        assert_eq!(1000, volume.try_decode::<u32>().unwrap());
+
+        // The type of a field can't be changed.
+        assert!(msg.update_field_by_name("BID", &100u32).is_err());
+    }
+
+    #[test]
+    fn update_missing_field_by_id() {
+        let mut msg = Msg::new().unwrap();
+        let mut field = Builder::new(&100.5).with_name("BID").with_id(1).encode();
+        msg.add_field(&mut field).unwrap();
+
+        // There's no name to give a new field, so it isn't added.
+        let kind = |r: Result<&mut Msg, TibrvError>| r.unwrap_err().kind();
+        assert_eq!(
+            ErrorKind::UnknownError(TIBRV_NOT_FOUND),
+            kind(msg.update_field_by_id(2, &101.0))
+        );
+        assert_eq!(
+            ErrorKind::UnknownError(TIBRV_INVALID_ARG),
+            kind(msg.update_field_by_id(0x1_0001, &101.0))
+        );
+        assert_eq!(1, msg.num_fields().unwrap());
+        let bid = msg.get_field_by_id(1).unwrap();
+        assert_eq!(100.5, bid.try_decode::<f64>().unwrap());
+    }
+
     #[test]
     fn copy_msg() {
         let mut msg = Msg::new().unwrap();
@@ -518,6 +781,109 @@ mod tests {
         assert_eq!(8, msg.byte_size().unwrap());
     }
 
+    #[test]
+    fn roundtrip_bytes() {
+        use chrono::NaiveDate;
+        use std::net::Ipv4Addr;
+
+        macro_rules! add {
+            ($msg:expr, $name:expr, $val:expr) => {{
+                let val = $val;
+                let mut field = Builder::new(&val).with_name($name).encode();
+                $msg.add_field(&mut field).unwrap();
+            }};
+        }
+
+        let mut nested = Msg::new().unwrap();
+        add!(nested, "inner", 1u8);
+
+        let mut msg = Msg::new().unwrap();
+        let data = CString::new("Hello world!").unwrap();
+        add!(msg, "string", data.as_c_str());
+        add!(msg, "message", &nested);
+        add!(msg, "u8", u8::MAX);
+        add!(msg, "u8s", &[1u8, 2, 3][..]);
+        add!(msg, "i8", i8::MIN);
+        add!(msg, "i8s", &[-1i8, 2, -3][..]);
+        add!(msg, "u16", u16::MAX);
+        add!(msg, "u16s", &[1u16, 2, 3][..]);
+        add!(msg, "i16", i16::MIN);
+        add!(msg, "i16s", &[-1i16, 2, -3][..]);
+        add!(msg, "u32", u32::MAX);
+        add!(msg, "u32s", &[1u32, 2, 3][..]);
+        add!(msg, "i32", i32::MIN);
+        add!(msg, "i32s", &[-1i32, 2, -3][..]);
+        add!(msg, "u64", u64::MAX);
+        add!(msg, "u64s", &[1u64, 2, 3][..]);
+        add!(msg, "i64", i64::MIN);
+        add!(msg, "i64s", &[-1i64, 2, -3][..]);
+        add!(msg, "f32", 1.5f32);
+        add!(msg, "f32s", &[1.5f32, -2.5][..]);
+        add!(msg, "f64", 1.5f64);
+        add!(msg, "f64s", &[1.5f64, -2.5][..]);
+        add!(msg, "bool", true);
+        add!(
+            msg,
+            "datetime",
+            NaiveDate::from_ymd_opt(2018, 7, 24)
+                .and_then(|d| d.and_hms_opt(1, 2, 3))
+                .unwrap()
+        );
+        add!(msg, "ipv4", Ipv4Addr::new(127, 0, 0, 1));
+        let mut field = tibrv_encode_port(7500, Some("ipport"), None);
+        msg.add_field(&mut field).unwrap();
+        let opaque = [0xdeu8, 0xad, 0xbe, 0xef];
+        let mut field = unsafe { tibrv_encode_opaque(&opaque, Some("opaque"), None) };
+        msg.add_field(&mut field).unwrap();
+
+        let bytes = msg.to_bytes().unwrap();
+        assert_eq!(msg.byte_size().unwrap() as usize, bytes.len());
+        assert_eq!(&bytes[..], msg.as_bytes().unwrap());
+
+        let copy = Msg::from_bytes(&bytes).unwrap();
+        assert_eq!(bytes, copy.to_bytes().unwrap());
+        assert_eq!(msg.to_text().unwrap(), copy.to_text().unwrap());
+        assert_eq!(msg.num_fields().unwrap(), copy.num_fields().unwrap());
+        for (original, copied) in msg.into_iter().zip(&copy) {
+            let (original, copied) = (original.unwrap(), copied.unwrap());
+            let (original, copied): (&MsgField, &MsgField) = (&original, &copied);
+            assert_eq!(original.name, copied.name);
+            assert_eq!(original.inner.type_, copied.inner.type_);
+            assert_eq!(original.inner.count, copied.inner.count);
+            assert!(copied.try_decode::<DecodedField>().is_ok());
+        }
+
+        let port = copy.get_field_by_name("ipport").unwrap();
+        assert_eq!(7500, tibrv_try_decode_port(&port).unwrap());
+        let opaque_field = copy.get_field_by_name("opaque").unwrap();
+        assert_eq!(
+            opaque,
+            unsafe { tibrv_try_decode_opaque::<u8>(&opaque_field) }.unwrap()
+        );
+        let nested_field = copy.get_field_by_name("message").unwrap();
+        match nested_field.try_decode::<DecodedField>().unwrap() {
+            DecodedField::Message(m) => {
+                let inner = m.get_field_by_name("inner").unwrap();
+                assert_eq!(1u8, inner.try_decode::<u8>().unwrap());
+            }
+            _ => panic!("Field did not decode as expected"),
+        }
+    }
+
+    #[test]
+    fn from_bytes_truncated() {
+        let mut msg = Msg::new().unwrap();
+        let mut field = Builder::new(&1u32).with_name("field").encode();
+        msg.add_field(&mut field).unwrap();
+
+        let bytes = msg.to_bytes().unwrap();
+        let kind = |b: &[u8]| Msg::from_bytes(b).unwrap_err().kind();
+        let corrupt = ErrorKind::UnknownError(TIBRV_CORRUPT_MSG);
+        assert_eq!(corrupt, kind(&bytes[..bytes.len() - 1]));
+        assert_eq!(corrupt, kind(&bytes[..4]));
+        assert_eq!(corrupt, kind(&[]));
+    }
+
     #[test]
     fn roundtrip_send_subject() {
         let mut msg = Msg::new().unwrap();
@@ -533,4 +899,47 @@ mod tests {
         assert!(msg.set_reply_subject("TEST.REPLY").is_ok());
         assert_eq!("TEST.REPLY", msg.get_reply_subject().unwrap().unwrap());
     }
+
+    #[test]
+    fn to_text() {
+        let mut msg = Msg::new().unwrap();
+        let data = CString::new("Hello World!").unwrap();
+        let mut field = Builder::new(&data.as_c_str()).with_name("message").encode();
+        msg.add_field(&mut field).unwrap();
+        assert_eq!("{message=\"Hello World!\"}", msg.to_text().unwrap());
+        assert_eq!(msg.to_text().unwrap(), msg.to_string());
+    }
+
+    #[test]
+    fn debug() {
+        let mut inner = Msg::new().unwrap();
+        let slice: &[u16] = &[1, 2, 3];
+        let mut field = Builder::new(&slice).with_name("LEGS").encode();
+        inner.add_field(&mut field).unwrap();
+
+        let mut msg = Msg::new().unwrap();
+        msg.set_send_subject("TEST.DEBUG").unwrap();
+        let nested = &inner;
+        let mut field = Builder::new(&nested).with_name("NESTED").encode();
+        msg.add_field(&mut field).unwrap();
+
+        let debug = format!("{:?}", msg);
+        assert!(debug.starts_with("Msg {"));
+        assert!(debug.contains("send_subject: Some(\"TEST.DEBUG\")"));
+        assert!(debug.contains("reply_subject: None"));
+        assert!(debug.contains("NESTED={LEGS="));
+    }
+
+    #[test]
+    fn debug_truncates() {
+        // Multi-byte characters, so the limit falls within a character.
+        let data = CString::new("\u{e9}".repeat(DEBUG_TEXT_LIMIT)).unwrap();
+        let mut field = Builder::new(&data.as_c_str()).with_name("LONG").encode();
+        let mut msg = Msg::new().unwrap();
+        msg.add_field(&mut field).unwrap();
+
+        let debug = format!("{:?}", msg);
+        assert!(debug.len() < DEBUG_TEXT_LIMIT + 200);
+        assert!(debug.contains("bytes truncated)"));
+    }
 }
@@ -0,0 +1,422 @@
+//! An in-memory transport for testing code built on `RvTransport`
+//!
+//! `MockTransport` routes messages between subscriptions entirely within
+//! the current process, following the Rendezvous rules for subject
+//! matching, inboxes and request timeouts. Clones of a `MockTransport`
+//! share the same message bus, so a clone can be moved into another
+//! thread to act as a server.
+//!
+//! ```no_run
+//! use tibrv::context::RvTransport;
+//! use tibrv::event::RvSubscription;
+//! use tibrv::message::Msg;
+//! use tibrv::mock::MockTransport;
+//!
+//! let tp = MockTransport::new();
+//! let sub = tp.subscribe("PRICES.>").unwrap();
+//!
+//! let mut msg = Msg::new().unwrap();
+//! msg.set_send_subject("PRICES.VOD.L").unwrap();
+//! tp.send(&mut msg).unwrap();
+//!
+//! assert!(sub.next().is_ok());
+//! ```
+
+use crate::context::RvTransport;
+use crate::errors::*;
+use crate::event::RvSubscription;
+use crate::message::Msg;
+use failure::*;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tibrv_sys::TIBRV_INVALID_SUBJECT;
+
+// Subjects are limited to 255 characters by Rendezvous.
+const MAX_SUBJECT_LENGTH: usize = 255;
+const INBOX_PREFIX: &str = "_INBOX.";
+
+struct Listener {
+    id: u64,
+    subject: String,
+    sender: mpsc::Sender<Msg>,
+}
+
+#[derive(Default)]
+struct Bus {
+    next_listener: u64,
+    next_inbox: u64,
+    listeners: Vec<Listener>,
+}
+
+/// An in-memory implementation of `RvTransport`.
+///
+/// Messages sent through a `MockTransport` are copied to every matching
+/// subscription on the same bus. Subjects may contain the `*` and `>`
+/// wildcards with their usual Rendezvous meanings, but messages sent to an
+/// inbox are only delivered to listeners on exactly that inbox subject.
+#[derive(Clone, Default)]
+pub struct MockTransport {
+    bus: Arc<Mutex<Bus>>,
+}
+
+impl MockTransport {
+    /// Construct a new `MockTransport` with an empty message bus.
+    pub fn new() -> Self {
+        MockTransport::default()
+    }
+}
+
+impl RvTransport for MockTransport {
+    type Subscription = MockSubscription;
+
+    fn send(&self, msg: &mut Msg) -> Result<(), TibrvError> {
+        let subject = match msg.get_send_subject()? {
+            Some(ref s) if is_valid_subject(s) && !has_wildcard(s) => s.clone(),
+            _ => Err(ErrorKind::from(TIBRV_INVALID_SUBJECT))?,
+        };
+        let inbox = subject.starts_with(INBOX_PREFIX);
+
+        let bus = self.bus.lock().unwrap();
+        for listener in &bus.listeners {
+            let matched = if inbox {
+                listener.subject == subject
+            } else {
+                subject_matches(&listener.subject, &subject)
+            };
+            if matched {
+                // A dropped receiver is removed from the bus by its
+                // MockSubscription, so a failed send can be ignored.
+                let _ = listener.sender.send(msg.try_clone()?);
+            }
+        }
+        Ok(())
+    }
+
+    fn subscribe(&self, subject: &str) -> Result<MockSubscription, TibrvError> {
+        if !is_valid_subject(subject) || subject.split('.').all(is_wildcard) {
+            Err(ErrorKind::from(TIBRV_INVALID_SUBJECT))?
+        }
+
+        let (send, recv) = mpsc::channel();
+        let mut bus = self.bus.lock().unwrap();
+        let id = bus.next_listener;
+        bus.next_listener += 1;
+        bus.listeners.push(Listener {
+            id,
+            subject: subject.to_owned(),
+            sender: send,
+        });
+
+        Ok(MockSubscription {
+            id,
+            bus: self.bus.clone(),
+            channel: recv,
+        })
+    }
+
+    fn create_inbox(&self) -> Result<String, TibrvError> {
+        let mut bus = self.bus.lock().unwrap();
+        let inbox = format!("{}MOCK.{}", INBOX_PREFIX, bus.next_inbox);
+        bus.next_inbox += 1;
+        Ok(inbox)
+    }
+
+    fn request(&self, msg: &mut Msg, timeout: Option<f64>) -> Result<Msg, TibrvError> {
+        let inbox = self.create_inbox()?;
+        let sub = self.subscribe(&inbox)?;
+
+        msg.set_reply_subject(&inbox)?;
+        self.send(msg)?;
+
+        // A negative timeout doesn't wait at all, while one which isn't a
+        // number or is too large for a `Duration` waits indefinitely.
+        let timeout = match timeout {
+            Some(t) if !t.is_nan() => Duration::try_from_secs_f64(t.max(0.0)).ok(),
+            _ => None,
+        };
+        match timeout {
+            None => sub.next(),
+            Some(t) => sub
+                .channel
+                .recv_timeout(t)
+                .map_err(|_| ErrorKind::Timeout.into()),
+        }
+    }
+}
+
+/// A subscription to a subject on a `MockTransport`.
+///
+/// The listener is removed from the message bus when this is dropped.
+pub struct MockSubscription {
+    id: u64,
+    bus: Arc<Mutex<Bus>>,
+    channel: mpsc::Receiver<Msg>,
+}
+
+impl RvSubscription for MockSubscription {
+    fn next(&self) -> Result<Msg, TibrvError> {
+        self.channel
+            .recv()
+            .context(ErrorKind::QueueError)
+            .map_err(TibrvError::from)
+    }
+
+    fn try_next(&self) -> Result<Msg, mpsc::TryRecvError> {
+        self.channel.try_recv()
+    }
+}
+
+impl Drop for MockSubscription {
+    fn drop(&mut self) {
+        if let Ok(mut bus) = self.bus.lock() {
+            let id = self.id;
+            bus.listeners.retain(|l| l.id != id);
+        }
+    }
+}
+
+fn is_wildcard(element: &str) -> bool {
+    element == "*" || element == ">"
+}
+
+fn has_wildcard(subject: &str) -> bool {
+    subject.split('.').any(is_wildcard)
+}
+
+// Subjects are made up of non-empty elements separated by '.', the '>'
+// wildcard may only appear as the final element.
+fn is_valid_subject(subject: &str) -> bool {
+    if subject.is_empty() || subject.len() > MAX_SUBJECT_LENGTH || !subject.is_ascii() {
+        return false;
+    }
+    let elements: Vec<&str> = subject.split('.').collect();
+    let last = elements.len() - 1;
+    elements
+        .iter()
+        .enumerate()
+        .all(|(i, e)| !e.is_empty() && (*e != ">" || i == last))
+}
+
+/// Test whether a subject matches a (possibly wildcarded) pattern.
+///
+/// Follows the Rendezvous wildcard rules: `*` matches exactly one
+/// element of the subject, and a trailing `>` matches one or more
+/// remaining elements. Wildcards only have special meaning when they
+/// make up a whole element.
+pub fn subject_matches(pattern: &str, subject: &str) -> bool {
+    let mut pattern = pattern.split('.');
+    let mut subject = subject.split('.');
+    loop {
+        match (pattern.next(), subject.next()) {
+            (Some(">"), Some(_)) => return true,
+            (Some("*"), Some(_)) => continue,
+            (Some(p), Some(s)) if p == s => continue,
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::Builder;
+    use std::thread;
+
+    #[test]
+    fn wildcard_matching() {
+        assert!(subject_matches("A.B.C", "A.B.C"));
+        assert!(!subject_matches("A.B.C", "A.B"));
+        assert!(!subject_matches("A.B", "A.B.C"));
+        assert!(subject_matches("A.*.C", "A.B.C"));
+        assert!(!subject_matches("A.*.C", "A.B.D"));
+        assert!(!subject_matches("A.*", "A.B.C"));
+        assert!(subject_matches("A.>", "A.B"));
+        assert!(subject_matches("A.>", "A.B.C.D"));
+        assert!(!subject_matches("A.>", "A"));
+        assert!(subject_matches("*.B.>", "A.B.C"));
+        // Wildcards only count as a whole element.
+        assert!(!subject_matches("A.B*", "A.BC"));
+        assert!(subject_matches("A.B*", "A.B*"));
+    }
+
+    #[test]
+    fn subject_validation() {
+        assert!(is_valid_subject("A.B.C"));
+        assert!(is_valid_subject("A.*.>"));
+        assert!(!is_valid_subject(""));
+        assert!(!is_valid_subject("A..C"));
+        assert!(!is_valid_subject("A.B."));
+        assert!(!is_valid_subject("A.>.C"));
+        assert!(!is_valid_subject(&"A".repeat(256)));
+    }
+
+    #[test]
+    fn send_recv() {
+        let tp = MockTransport::new();
+        let wild = tp.subscribe("TEST.*").unwrap();
+        let exact = tp.subscribe("TEST.MOCK").unwrap();
+        let other = tp.subscribe("OTHER.>").unwrap();
+
+        let mut msg = Msg::new().unwrap();
+        msg.set_send_subject("TEST.MOCK").unwrap();
+        tp.send(&mut msg).unwrap();
+
+        assert!(wild.try_next().is_ok());
+        assert!(exact.try_next().is_ok());
+        assert_eq!(Err(mpsc::TryRecvError::Empty), other.try_next().map(|_| ()));
+    }
+
+    #[test]
+    fn invalid_subjects() {
+        let tp = MockTransport::new();
+        assert!(tp.subscribe(">").is_err());
+        assert!(tp.subscribe("A..B").is_err());
+
+        let mut msg = Msg::new().unwrap();
+        assert!(tp.send(&mut msg).is_err()); // No send subject
+        msg.set_send_subject("A.*").unwrap();
+        assert!(tp.send(&mut msg).is_err());
+    }
+
+    #[test]
+    fn unsubscribe_on_drop() {
+        let tp = MockTransport::new();
+        {
+            let _sub = tp.subscribe("TEST.DROP").unwrap();
+            assert_eq!(1, tp.bus.lock().unwrap().listeners.len());
+        }
+        assert_eq!(0, tp.bus.lock().unwrap().listeners.len());
+    }
+
+    #[test]
+    fn inbox_not_matched_by_wildcards() {
+        let tp = MockTransport::new();
+        let inbox = tp.create_inbox().unwrap();
+        assert!(inbox.starts_with("_INBOX."));
+        assert_ne!(inbox, tp.create_inbox().unwrap());
+
+        let wild = tp.subscribe("_INBOX.>").unwrap();
+        let exact = tp.subscribe(&inbox).unwrap();
+
+        let mut msg = Msg::new().unwrap();
+        msg.set_send_subject(&inbox).unwrap();
+        tp.send(&mut msg).unwrap();
+
+        assert!(exact.try_next().is_ok());
+        assert!(wild.try_next().is_err());
+    }
+
+    #[test]
+    fn timeout_request() {
+        let tp = MockTransport::new();
+        let mut msg = Msg::new().unwrap();
+        msg.set_send_subject("REQUEST.TEST").unwrap();
+        let req = tp.request(&mut msg, Some(0.1));
+        let _ = req.map_err(|e| assert_eq!(ErrorKind::Timeout, e.kind()));
+    }
+
+    #[test]
+    fn request_reply() {
+        let tp = MockTransport::new();
+        let sub = tp.subscribe("REQUEST.TEST").unwrap();
+
+        let server_tp = tp.clone();
+        let server = thread::spawn(move || {
+            let request = sub.next().unwrap();
+            let mut reply = Msg::new().unwrap();
+            server_tp.send_reply(&mut reply, &request).unwrap();
+        });
+
+        let mut msg = Msg::new().unwrap();
+        msg.set_send_subject("REQUEST.TEST").unwrap();
+        let reply = tp.request(&mut msg, Some(5.0)).unwrap();
+        assert!(reply
+            .get_send_subject()
+            .unwrap()
+            .unwrap()
+            .starts_with("_INBOX."));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn request_without_finite_timeout() {
+        let tp = MockTransport::new();
+        let sub = tp.subscribe("REQUEST.TEST").unwrap();
+
+        let server_tp = tp.clone();
+        let server = thread::spawn(move || {
+            for _ in 0..3 {
+                let request = sub.next().unwrap();
+                let mut reply = Msg::new().unwrap();
+                server_tp.send_reply(&mut reply, &request).unwrap();
+            }
+        });
+
+        // Each waits for the reply rather than panicking.
+        for &timeout in &[f64::INFINITY, f64::NAN, f64::MAX] {
+            let mut msg = Msg::new().unwrap();
+            msg.set_send_subject("REQUEST.TEST").unwrap();
+            assert!(tp.request(&mut msg, Some(timeout)).is_ok());
+        }
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn send_reply_not_a_request() {
+        let tp = MockTransport::new();
+        let server_tp = tp.clone();
+        let (ready, wait) = mpsc::channel();
+        let server = thread::spawn(move || {
+            // Subscribe before signalling, so the message isn't missed.
+            let sub = server_tp.subscribe("SERVE.TEST").unwrap();
+            ready.send(()).unwrap();
+            let request = sub.next().unwrap();
+            let mut reply = Msg::new().unwrap();
+            server_tp.send_reply(&mut reply, &request)
+        });
+
+        wait.recv().unwrap();
+        let mut msg = Msg::new().unwrap();
+        msg.set_send_subject("SERVE.TEST").unwrap();
+        tp.send(&mut msg).unwrap();
+        match server.join().unwrap() {
+            Err(e) => assert_eq!(ErrorKind::NotARequest, e.kind()),
+            Ok(_) => panic!("Expected an error"),
+        }
+    }
+
+    #[test]
+    fn serve_skips_non_requests() {
+        let tp = MockTransport::new();
+        let server_tp = tp.clone();
+        let server = thread::spawn(move || {
+            server_tp.serve("SERVE.SKIP", |msg| {
+                if msg.get_field_by_name("fail").is_ok() {
+                    Err(ErrorKind::FieldTypeError)?
+                }
+                msg.try_clone()
+            })
+        });
+
+        let mut msg = Msg::new().unwrap();
+        msg.set_send_subject("SERVE.SKIP").unwrap();
+        // The server may not have subscribed yet, so retry the request.
+        let reply = (0..50).find_map(|_| tp.request(&mut msg, Some(0.1)).ok());
+        assert!(reply.is_some());
+
+        let mut msg = Msg::new().unwrap();
+        msg.set_send_subject("SERVE.SKIP").unwrap();
+        tp.send(&mut msg).unwrap();
+        assert!(tp.request(&mut msg, Some(5.0)).is_ok());
+
+        let mut field = Builder::new(&true).with_name("fail").encode();
+        msg.add_field(&mut field).unwrap();
+        tp.send(&mut msg).unwrap();
+        match server.join().unwrap() {
+            Err(e) => assert_eq!(ErrorKind::FieldTypeError, e.kind()),
+            Ok(_) => panic!("Expected an error"),
+        }
+    }
+}
@@ -0,0 +1,50 @@
+//! Fixtures shared by the unit tests
+
+use crate::context::{RvCtx, Transport};
+use crate::event::Subscription;
+use crate::message::Msg;
+use std::sync::mpsc;
+use std::thread;
+
+// Run a server on its own thread, with its own intra-process transport.
+//
+// Returns once the server has subscribed to `subject`, so requests sent
+// afterwards aren't missed. The server handles requests with `f`, and
+// the thread finishes when `f` returns.
+pub(crate) fn spawn_server<F>(ctx: &RvCtx, subject: &str, f: F) -> thread::JoinHandle<()>
+where
+    F: FnOnce(&Transport, &Subscription) + Send + 'static,
+{
+    spawn_server_with(ctx, subject, Transport::intra_process, f)
+}
+
+// Run a server as with `spawn_server`, on the transport created by `tp`.
+pub(crate) fn spawn_server_with<T, F>(
+    ctx: &RvCtx,
+    subject: &str,
+    tp: T,
+    f: F,
+) -> thread::JoinHandle<()>
+where
+    T: FnOnce(&RvCtx) -> Transport + Send + 'static,
+    F: FnOnce(&Transport, &Subscription) + Send + 'static,
+{
+    let (ready, wait) = mpsc::channel();
+    let ctx = ctx.clone();
+    let subject = subject.to_owned();
+    let server = thread::spawn(move || {
+        let tp = tp(&ctx);
+        let sub = tp.subscribe(&subject).unwrap();
+        ready.send(()).unwrap();
+        f(&tp, &sub)
+    });
+    wait.recv().unwrap();
+    server
+}
+
+// Answer a single request with an empty reply.
+pub(crate) fn reply(tp: &Transport, sub: &Subscription) {
+    let request = sub.next().unwrap();
+    let mut reply = Msg::new().unwrap();
+    tp.send_reply(&mut reply, &request).unwrap();
+}
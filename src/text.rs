@@ -0,0 +1,549 @@
+//! Parser for the Rendezvous text message format
+//!
+//! This is the format produced by `tibrvMsg_ConvertToString`, for example
+//! `{SYMBOL="VOD.L" BID=101.5 LEGS={QTY=100}}`. The text form doesn't
+//! record the width of numeric fields, so they are inferred unless a
+//! suffix gives the type explicitly, see `Msg::from_text`.
+
+use crate::errors::*;
+use crate::field::{tibrv_encode_port, Builder, Encodable};
+use crate::message::Msg;
+use chrono::NaiveDateTime;
+use std::ffi::CString;
+use std::net::Ipv4Addr;
+
+const SUFFIXES: &[(&str, Kind)] = &[
+    ("u8", Kind::U8),
+    ("i8", Kind::I8),
+    ("u16", Kind::U16),
+    ("i16", Kind::I16),
+    ("u32", Kind::U32),
+    ("i32", Kind::I32),
+    ("u64", Kind::U64),
+    ("i64", Kind::I64),
+    ("f32", Kind::F32),
+    ("f64", Kind::F64),
+    ("port", Kind::Port),
+];
+
+const DATETIME_FORMATS: &[&str] = &["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    U64,
+    I64,
+    F32,
+    F64,
+    Port,
+}
+
+#[derive(Clone, Copy)]
+struct Position {
+    line: u32,
+    column: u32,
+}
+
+impl From<Position> for TibrvError {
+    fn from(pos: Position) -> TibrvError {
+        ErrorKind::ParseError {
+            line: pos.line,
+            column: pos.column,
+        }
+        .into()
+    }
+}
+
+// A number as written in the text, with its type if one was given.
+struct Number<'a> {
+    digits: &'a str,
+    kind: Option<Kind>,
+    pos: Position,
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    offset: usize,
+    pos: Position,
+}
+
+/// Parse a complete message from `text`.
+pub(crate) fn parse(text: &str) -> Result<Msg, TibrvError> {
+    let mut parser = Parser {
+        text,
+        offset: 0,
+        pos: Position { line: 1, column: 1 },
+    };
+    parser.skip_whitespace();
+    let msg = parser.message()?;
+    parser.skip_whitespace();
+    match parser.peek() {
+        None => Ok(msg),
+        Some(_) => Err(parser.pos)?,
+    }
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.offset..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.offset += c.len_utf8();
+        if c == '\n' {
+            self.pos.line += 1;
+            self.pos.column = 1;
+        } else {
+            self.pos.column += 1;
+        }
+        Some(c)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), TibrvError> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.bump();
+                Ok(())
+            }
+            _ => Err(self.pos)?,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.bump();
+        }
+    }
+
+    // Fields and array elements may be separated by commas as well as
+    // whitespace.
+    fn skip_separators(&mut self) {
+        while self.peek().is_some_and(|c| c.is_whitespace() || c == ',') {
+            self.bump();
+        }
+    }
+
+    // A run of characters up to whitespace or punctuation.
+    fn word(&mut self) -> &'a str {
+        let start = self.offset;
+        while self.peek().is_some_and(|c| !is_delimiter(c)) {
+            self.bump();
+        }
+        &self.text[start..self.offset]
+    }
+
+    fn message(&mut self) -> Result<Msg, TibrvError> {
+        self.expect('{')?;
+        let mut msg = Msg::new()?;
+        loop {
+            self.skip_separators();
+            match self.peek() {
+                Some('}') => {
+                    self.bump();
+                    return Ok(msg);
+                }
+                Some(c) if c == '=' || !is_delimiter(c) => self.field(&mut msg)?,
+                _ => Err(self.pos)?,
+            }
+        }
+    }
+
+    fn field(&mut self, msg: &mut Msg) -> Result<(), TibrvError> {
+        let name_pos = self.pos;
+        let name = self.word();
+        let name = CString::new(name).map(|_| name).or(Err(name_pos))?;
+        self.skip_whitespace();
+        self.expect('=')?;
+        self.skip_whitespace();
+
+        let pos = self.pos;
+        match self.peek() {
+            Some('"') => {
+                let value = self.string()?;
+                add(msg, name, &value.as_c_str())
+            }
+            Some('{') => {
+                let nested = self.message()?;
+                add(msg, name, &&nested)
+            }
+            Some('[') => self.array(msg, name),
+            Some(c) if !is_delimiter(c) => {
+                let word = self.datetime_word();
+                add_scalar(msg, name, word, pos)
+            }
+            _ => Err(pos)?,
+        }
+    }
+
+    fn string(&mut self) -> Result<CString, TibrvError> {
+        let start = self.pos;
+        self.expect('"')?;
+        let mut value = String::new();
+        loop {
+            let pos = self.pos;
+            match self.bump() {
+                Some('"') => break,
+                Some('\\') => match self.bump() {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some('r') => value.push('\r'),
+                    Some(c @ '"') | Some(c @ '\\') => value.push(c),
+                    _ => Err(pos)?,
+                },
+                Some(c) => value.push(c),
+                None => Err(self.pos)?,
+            }
+        }
+        CString::new(value).or(Err(start)).map_err(TibrvError::from)
+    }
+
+    // Rendezvous writes datetimes as `2019-03-05 12:00:00.000000000Z`,
+    // so a date may be followed by a space and a time.
+    fn datetime_word(&mut self) -> &'a str {
+        let start = self.offset;
+        let word = self.word();
+        let rest = &self.text[self.offset..];
+        let time_follows =
+            rest.starts_with(' ') && rest[1..].chars().next().is_some_and(|c| c.is_ascii_digit());
+        if looks_like_date(word) && word.len() == 10 && time_follows {
+            self.bump();
+            self.word();
+        }
+        &self.text[start..self.offset]
+    }
+
+    fn array(&mut self, msg: &mut Msg, name: &str) -> Result<(), TibrvError> {
+        let start = self.pos;
+        self.expect('[')?;
+        let mut elements = Vec::new();
+        loop {
+            self.skip_separators();
+            let pos = self.pos;
+            match self.peek() {
+                Some(']') => {
+                    self.bump();
+                    break;
+                }
+                Some(c) if !is_delimiter(c) => {
+                    let (digits, kind) = split_suffix(self.word());
+                    elements.push(Number { digits, kind, pos });
+                }
+                _ => Err(pos)?,
+            }
+        }
+
+        // Elements without a suffix take the type of those with one.
+        let mut kind = None;
+        for element in &elements {
+            match (kind, element.kind) {
+                (_, Some(Kind::Port)) => Err(element.pos)?,
+                (Some(k), Some(e)) if k != e => Err(element.pos)?,
+                (_, Some(e)) => kind = Some(e),
+                _ => (),
+            }
+        }
+        let kind = kind.unwrap_or_else(|| {
+            elements
+                .iter()
+                .map(|e| infer(e.digits))
+                .max_by_key(|k| widening_rank(*k))
+                .unwrap_or(Kind::I32)
+        });
+
+        macro_rules! add_array {
+            ($ty:ty) => {{
+                let mut values: Vec<$ty> = Vec::with_capacity(elements.len());
+                for element in &elements {
+                    values.push(element.digits.parse().or(Err(element.pos))?);
+                }
+                add(msg, name, &values.as_slice())
+            }};
+        }
+
+        match kind {
+            Kind::U8 => add_array!(u8),
+            Kind::I8 => add_array!(i8),
+            Kind::U16 => add_array!(u16),
+            Kind::I16 => add_array!(i16),
+            Kind::U32 => add_array!(u32),
+            Kind::I32 => add_array!(i32),
+            Kind::U64 => add_array!(u64),
+            Kind::I64 => add_array!(i64),
+            Kind::F32 => add_array!(f32),
+            Kind::F64 => add_array!(f64),
+            Kind::Port => Err(start)?,
+        }
+    }
+}
+
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || "{}[]\",=".contains(c)
+}
+
+fn looks_like_date(word: &str) -> bool {
+    let bytes = word.as_bytes();
+    bytes.len() >= 10
+        && bytes[..4].iter().all(u8::is_ascii_digit)
+        && bytes[4] == b'-'
+        && bytes[7] == b'-'
+}
+
+fn split_suffix(word: &str) -> (&str, Option<Kind>) {
+    SUFFIXES
+        .iter()
+        .find(|(suffix, _)| word.len() > suffix.len() && word.ends_with(suffix))
+        .map_or((word, None), |(suffix, kind)| {
+            (&word[..word.len() - suffix.len()], Some(*kind))
+        })
+}
+
+// The narrowest of i32, i64, u64 or f64 which can hold the number.
+fn infer(digits: &str) -> Kind {
+    if digits.contains(['.', 'e', 'E']) {
+        Kind::F64
+    } else if digits.parse::<i32>().is_ok() {
+        Kind::I32
+    } else if digits.parse::<i64>().is_ok() {
+        Kind::I64
+    } else if digits.parse::<u64>().is_ok() {
+        Kind::U64
+    } else {
+        Kind::F64
+    }
+}
+
+fn widening_rank(kind: Kind) -> u8 {
+    match kind {
+        Kind::I32 => 0,
+        Kind::I64 => 1,
+        Kind::U64 => 2,
+        _ => 3,
+    }
+}
+
+fn add_scalar(msg: &mut Msg, name: &str, word: &str, pos: Position) -> Result<(), TibrvError> {
+    match word {
+        "true" => return add(msg, name, &true),
+        "false" => return add(msg, name, &false),
+        _ => (),
+    }
+
+    if looks_like_date(word) {
+        let trimmed = word.trim_end_matches('Z');
+        let datetime = DATETIME_FORMATS
+            .iter()
+            .find_map(|f| NaiveDateTime::parse_from_str(trimmed, f).ok())
+            .ok_or(pos)?;
+        return add(msg, name, &datetime);
+    }
+
+    if word.matches('.').count() == 3 {
+        if let Ok(addr) = word.parse::<Ipv4Addr>() {
+            return add(msg, name, &addr);
+        }
+    }
+
+    let (digits, kind) = split_suffix(word);
+    let kind = kind.unwrap_or_else(|| infer(digits));
+
+    macro_rules! add_number {
+        ($ty:ty) => {{
+            let value: $ty = digits.parse().or(Err(pos))?;
+            add(msg, name, &value)
+        }};
+    }
+
+    match kind {
+        Kind::U8 => add_number!(u8),
+        Kind::I8 => add_number!(i8),
+        Kind::U16 => add_number!(u16),
+        Kind::I16 => add_number!(i16),
+        Kind::U32 => add_number!(u32),
+        Kind::I32 => add_number!(i32),
+        Kind::U64 => add_number!(u64),
+        Kind::I64 => add_number!(i64),
+        Kind::F32 => add_number!(f32),
+        Kind::F64 => add_number!(f64),
+        Kind::Port => {
+            let port: u16 = digits.parse().or(Err(pos))?;
+            let mut field = tibrv_encode_port(port, field_name(name), None);
+            msg.add_field(&mut field).map(|_| ())
+        }
+    }
+}
+
+// Unnamed fields are written with nothing before the `=`.
+fn field_name(name: &str) -> Option<&str> {
+    if name.is_empty() {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+fn add<T: Encodable>(msg: &mut Msg, name: &str, value: &T) -> Result<(), TibrvError> {
+    let mut field = match field_name(name) {
+        Some(name) => Builder::new(value).with_name(name).encode(),
+        None => Builder::new(value).encode(),
+    };
+    msg.add_field(&mut field).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::{tibrv_try_decode_port, DecodedField};
+    use chrono::NaiveDate;
+
+    fn parse_error(text: &str) -> (u32, u32) {
+        match parse(text).map(|_| ()).unwrap_err().kind() {
+            ErrorKind::ParseError { line, column } => (line, column),
+            kind => panic!("Unexpected error {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn scalars() {
+        let msg = parse(r#"{SYMBOL="VOD.L" BID=101.5 QTY=100 BIG=5000000000 OPEN=true}"#).unwrap();
+        let field = msg.get_field_by_name("SYMBOL").unwrap();
+        match field.try_decode::<DecodedField>().unwrap() {
+            DecodedField::String(s) => assert_eq!("VOD.L", s.to_str().unwrap()),
+            _ => panic!("Expected a string"),
+        }
+        assert_eq!(
+            101.5,
+            msg.get_field_by_name("BID")
+                .unwrap()
+                .try_decode::<f64>()
+                .unwrap()
+        );
+        assert_eq!(
+            100,
+            msg.get_field_by_name("QTY")
+                .unwrap()
+                .try_decode::<i32>()
+                .unwrap()
+        );
+        assert_eq!(
+            5_000_000_000,
+            msg.get_field_by_name("BIG")
+                .unwrap()
+                .try_decode::<i64>()
+                .unwrap()
+        );
+        assert!(msg
+            .get_field_by_name("OPEN")
+            .unwrap()
+            .try_decode::<bool>()
+            .unwrap());
+    }
+
+    #[test]
+    fn suffixes() {
+        let msg = parse("{A=1u8, B=-2i16 C=3u64 D=1.5f32 E=7500port}").unwrap();
+        assert_eq!(
+            1,
+            msg.get_field_by_name("A")
+                .unwrap()
+                .try_decode::<u8>()
+                .unwrap()
+        );
+        assert_eq!(
+            -2,
+            msg.get_field_by_name("B")
+                .unwrap()
+                .try_decode::<i16>()
+                .unwrap()
+        );
+        assert_eq!(
+            3,
+            msg.get_field_by_name("C")
+                .unwrap()
+                .try_decode::<u64>()
+                .unwrap()
+        );
+        assert_eq!(
+            1.5,
+            msg.get_field_by_name("D")
+                .unwrap()
+                .try_decode::<f32>()
+                .unwrap()
+        );
+        let port = msg.get_field_by_name("E").unwrap();
+        assert_eq!(7500, tibrv_try_decode_port(&port).unwrap());
+    }
+
+    #[test]
+    fn strings() {
+        let msg = parse(r#"{S="say \"hi\"\n" EMPTY=""}"#).unwrap();
+        let field = msg.get_field_by_name("S").unwrap();
+        let s = field.try_decode::<&std::ffi::CStr>().unwrap();
+        assert_eq!("say \"hi\"\n", s.to_str().unwrap());
+        assert!(msg.get_field_by_name("EMPTY").is_ok());
+    }
+
+    #[test]
+    fn nested_and_arrays() {
+        let msg = parse("{LEGS={QTY=[1 2 3] PX=[1, 2.5]} BYTES=[1u8 2 3]}").unwrap();
+        let legs = msg.get_field_by_name("LEGS").unwrap();
+        match legs.try_decode::<DecodedField>().unwrap() {
+            DecodedField::Message(legs) => {
+                let qty = legs.get_field_by_name("QTY").unwrap();
+                assert_eq!([1, 2, 3], qty.try_decode::<&[i32]>().unwrap());
+                let px = legs.get_field_by_name("PX").unwrap();
+                assert_eq!([1.0, 2.5], px.try_decode::<&[f64]>().unwrap());
+            }
+            _ => panic!("Expected a message"),
+        }
+        let bytes = msg.get_field_by_name("BYTES").unwrap();
+        assert_eq!([1, 2, 3], bytes.try_decode::<&[u8]>().unwrap());
+    }
+
+    #[test]
+    fn datetimes_and_addresses() {
+        let msg =
+            parse("{TIME=2019-03-05 12:30:00.250000000Z ISO=2019-03-05T12:30:00 HOST=192.168.1.1}")
+                .unwrap();
+        let expected = NaiveDate::from_ymd_opt(2019, 3, 5)
+            .and_then(|d| d.and_hms_milli_opt(12, 30, 0, 250))
+            .unwrap();
+        let time = msg.get_field_by_name("TIME").unwrap();
+        assert_eq!(expected, time.try_decode::<NaiveDateTime>().unwrap());
+        let iso = msg.get_field_by_name("ISO").unwrap();
+        assert_eq!(
+            expected - chrono::Duration::milliseconds(250),
+            iso.try_decode::<NaiveDateTime>().unwrap()
+        );
+        let host = msg.get_field_by_name("HOST").unwrap();
+        assert_eq!(
+            Ipv4Addr::new(192, 168, 1, 1),
+            host.try_decode::<Ipv4Addr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn roundtrip_text() {
+        let text = r#"{SYMBOL="VOD.L" BID=101.5 LEGS={QTY=100}}"#;
+        let msg = parse(text).unwrap();
+        assert_eq!(3, msg.num_fields().unwrap());
+        let reparsed = parse(&msg.to_text().unwrap()).unwrap();
+        assert_eq!(msg.to_text().unwrap(), reparsed.to_text().unwrap());
+    }
+
+    #[test]
+    fn error_positions() {
+        assert_eq!((1, 1), parse_error("SYMBOL=1"));
+        assert_eq!((1, 8), parse_error("{SYMBOL}"));
+        assert_eq!((2, 4), parse_error("{A=1\n B=x}"));
+        assert_eq!((1, 11), parse_error("{A=[1 2u8 3i16]}"));
+        assert_eq!((1, 9), parse_error("{A=\"open"));
+        assert_eq!((1, 4), parse_error("{A=300u8}"));
+        assert_eq!((1, 7), parse_error("{A=1} B=2"));
+    }
+}
@@ -0,0 +1,166 @@
+//! A pure Rust codec for the Rendezvous message wire format
+//!
+//! `Msg` is a handle to a message owned by libtibrv, so inspecting one
+//! needs a Rendezvous installation. This module re-exports the
+//! `tibrv-wire` crate, which reads and writes the byte format produced by
+//! `Msg::to_bytes` without making any Rendezvous calls. Depend on
+//! `tibrv-wire` directly to decode messages where libtibrv isn't
+//! available.
+//!
+//! ```no_run
+//! use tibrv::message::Msg;
+//! use tibrv::wire::{Message, Value};
+//!
+//! let msg = Msg::from_text(r#"{SYMBOL="VOD.L" BID=101.5}"#).unwrap();
+//! let bytes = msg.to_bytes().unwrap();
+//!
+//! // Later, perhaps without libtibrv available
+//! let decoded = Message::from_bytes(&bytes).unwrap();
+//! let bid = decoded.get_field_by_name("BID").unwrap();
+//! assert_eq!(Value::F64(101.5), bid.value);
+//! ```
+
+pub use tibrv_wire::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::{tibrv_encode_opaque, tibrv_encode_port, Builder};
+    use crate::message::Msg;
+    use chrono::{NaiveDate, NaiveDateTime};
+    use std::ffi::CString;
+    use std::net::Ipv4Addr;
+
+    fn named(name: &str, value: Value) -> Field {
+        Field {
+            name: Some(name.to_owned()),
+            id: None,
+            value,
+        }
+    }
+
+    fn datetime() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2018, 7, 24)
+            .and_then(|d| d.and_hms_nano_opt(1, 2, 3, 456_789))
+            .unwrap()
+    }
+
+    // A message with a field of every type, built through libtibrv,
+    // along with the `Message` it should decode as.
+    fn every_type() -> (Msg, Message) {
+        macro_rules! add {
+            ($msg:expr, $name:expr, $val:expr) => {{
+                let val = $val;
+                let mut field = Builder::new(&val).with_name($name).encode();
+                $msg.add_field(&mut field).unwrap();
+            }};
+        }
+
+        let mut nested = Msg::new().unwrap();
+        add!(nested, "inner", 1u8);
+
+        let mut msg = Msg::new().unwrap();
+        let data = CString::new("Hello world!").unwrap();
+        add!(msg, "string", data.as_c_str());
+        add!(msg, "message", &nested);
+        add!(msg, "u8", u8::MAX);
+        add!(msg, "u8s", &[1u8, 2, 3][..]);
+        add!(msg, "i8", i8::MIN);
+        add!(msg, "i8s", &[-1i8, 2, -3][..]);
+        add!(msg, "u16", u16::MAX);
+        add!(msg, "u16s", &[1u16, 2, 3][..]);
+        add!(msg, "i16", i16::MIN);
+        add!(msg, "i16s", &[-1i16, 2, -3][..]);
+        add!(msg, "u32", u32::MAX);
+        add!(msg, "u32s", &[1u32, 2, 3][..]);
+        add!(msg, "i32", i32::MIN);
+        add!(msg, "i32s", &[-1i32, 2, -3][..]);
+        add!(msg, "u64", u64::MAX);
+        add!(msg, "u64s", &[1u64, 2, 3][..]);
+        add!(msg, "i64", i64::MIN);
+        add!(msg, "i64s", &[-1i64, 2, -3][..]);
+        add!(msg, "f32", 1.5f32);
+        add!(msg, "f32s", &[1.5f32, -2.5][..]);
+        add!(msg, "f64", 1.5f64);
+        add!(msg, "f64s", &[1.5f64, -2.5][..]);
+        add!(msg, "bool", true);
+        add!(msg, "datetime", datetime());
+        add!(msg, "ipv4", Ipv4Addr::new(127, 0, 0, 1));
+        let mut port = tibrv_encode_port(7500, Some("ipport"), None);
+        msg.add_field(&mut port).unwrap();
+        let opaque = [0xdeu8, 0xad, 0xbe, 0xef];
+        let mut field = unsafe { tibrv_encode_opaque(&opaque, Some("opaque"), None) };
+        msg.add_field(&mut field).unwrap();
+        // Large enough for `u16` and `u32` data sizes.
+        let long = vec![7u16; 1000];
+        add!(msg, "u16s long", &long[..]);
+        let long_opaque = vec![7u8; 100_000];
+        let mut field = unsafe { tibrv_encode_opaque(&long_opaque, Some("opaque long"), None) };
+        msg.add_field(&mut field).unwrap();
+        let mut field = Builder::new(&5u32).with_name("with id").with_id(7).encode();
+        msg.add_field(&mut field).unwrap();
+
+        let expected = Message {
+            fields: vec![
+                named("string", Value::String(data)),
+                named(
+                    "message",
+                    Value::Message(Message {
+                        fields: vec![named("inner", Value::U8(1))],
+                    }),
+                ),
+                named("u8", Value::U8(u8::MAX)),
+                named("u8s", Value::U8Array(vec![1, 2, 3])),
+                named("i8", Value::I8(i8::MIN)),
+                named("i8s", Value::I8Array(vec![-1, 2, -3])),
+                named("u16", Value::U16(u16::MAX)),
+                named("u16s", Value::U16Array(vec![1, 2, 3])),
+                named("i16", Value::I16(i16::MIN)),
+                named("i16s", Value::I16Array(vec![-1, 2, -3])),
+                named("u32", Value::U32(u32::MAX)),
+                named("u32s", Value::U32Array(vec![1, 2, 3])),
+                named("i32", Value::I32(i32::MIN)),
+                named("i32s", Value::I32Array(vec![-1, 2, -3])),
+                named("u64", Value::U64(u64::MAX)),
+                named("u64s", Value::U64Array(vec![1, 2, 3])),
+                named("i64", Value::I64(i64::MIN)),
+                named("i64s", Value::I64Array(vec![-1, 2, -3])),
+                named("f32", Value::F32(1.5)),
+                named("f32s", Value::F32Array(vec![1.5, -2.5])),
+                named("f64", Value::F64(1.5)),
+                named("f64s", Value::F64Array(vec![1.5, -2.5])),
+                named("bool", Value::Bool(true)),
+                named("datetime", Value::DateTime(datetime())),
+                named("ipv4", Value::Ipv4(Ipv4Addr::new(127, 0, 0, 1))),
+                named("ipport", Value::IpPort(7500)),
+                named("opaque", Value::Opaque(opaque.to_vec())),
+                named("u16s long", Value::U16Array(long)),
+                named("opaque long", Value::Opaque(long_opaque)),
+                Field {
+                    name: Some("with id".to_owned()),
+                    id: Some(7),
+                    value: Value::U32(5),
+                },
+            ],
+        };
+        (msg, expected)
+    }
+
+    #[test]
+    fn decode_tibrv_bytes() {
+        let (msg, expected) = every_type();
+        let decoded = Message::from_bytes(msg.as_bytes().unwrap()).unwrap();
+        assert_eq!(expected, decoded);
+        assert_eq!(Some(&decoded.fields[29]), decoded.get_field_by_id(7));
+    }
+
+    #[test]
+    fn encode_matches_tibrv() {
+        let (msg, expected) = every_type();
+        let bytes = expected.to_bytes().unwrap();
+        assert_eq!(msg.to_bytes().unwrap(), bytes);
+
+        let rebuilt = Msg::from_bytes(&bytes).unwrap();
+        assert_eq!(msg.to_text().unwrap(), rebuilt.to_text().unwrap());
+    }
+}
@@ -10,6 +10,10 @@ use std::fmt;
 
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 
+// This is defined in tibrv.h as a cast, `((tibrvTransport) 10)`,
+// which bindgen is unable to translate, so we mirror it here.
+pub const TIBRV_PROCESS_TRANSPORT: tibrvTransport = 10;
+
 impl From<bool> for tibrv_bool {
     fn from(boolean: bool) -> Self {
         if boolean {
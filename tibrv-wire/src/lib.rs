@@ -0,0 +1,589 @@
+//! A pure Rust codec for the TIBCO Rendezvous message wire format
+//!
+//! Messages in the `tibrv` crate are handles to memory owned by libtibrv,
+//! so inspecting one needs a Rendezvous installation. This crate reads and
+//! writes the self-describing byte format produced by `Msg::to_bytes` into
+//! an owned tree of fields, without linking against libtibrv, so stored or
+//! captured messages can be handled on any machine.
+//!
+//! A message is laid out as:
+//!
+//! * The total size of the message in bytes, including this header, as a
+//!   big-endian `u32`, followed by four magic bytes.
+//! * Each field in turn, made up of:
+//!   * One byte giving the size of the name including its NUL terminator,
+//!     with the high bit set if a field id follows. Unnamed fields have
+//!     a size of zero.
+//!   * The name, then the id as a big-endian `u16` if present.
+//!   * One byte holding the `TIBRVMSG_*` type of the field, as defined in
+//!     `tibrv/msg.h`.
+//!   * The size of the data. Sizes below `0x79` are a single byte, larger
+//!     sizes are written as `0x79` followed by a `u16`, or `0x7a` followed
+//!     by a `u32`.
+//!   * The data, with numbers in network byte order. Nested messages
+//!     include their own header.
+//!
+//! ```
+//! use tibrv_wire::{Field, Message, Value};
+//!
+//! let mut msg = Message::new();
+//! msg.fields.push(Field {
+//!     name: Some("BID".to_owned()),
+//!     id: None,
+//!     value: Value::F64(101.5),
+//! });
+//! let bytes = msg.to_bytes().unwrap();
+//!
+//! let decoded = Message::from_bytes(&bytes).unwrap();
+//! let bid = decoded.get_field_by_name("BID").unwrap();
+//! assert_eq!(Value::F64(101.5), bid.value);
+//! ```
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Timelike};
+use std::convert::TryFrom;
+use std::error;
+use std::ffi::CString;
+use std::fmt;
+use std::net::Ipv4Addr;
+
+// Field types, from `tibrv/msg.h`.
+const TIBRVMSG_MSG: u8 = 1;
+const TIBRVMSG_DATETIME: u8 = 3;
+const TIBRVMSG_OPAQUE: u8 = 7;
+const TIBRVMSG_STRING: u8 = 8;
+const TIBRVMSG_BOOL: u8 = 9;
+const TIBRVMSG_I8: u8 = 14;
+const TIBRVMSG_U8: u8 = 15;
+const TIBRVMSG_I16: u8 = 16;
+const TIBRVMSG_U16: u8 = 17;
+const TIBRVMSG_I32: u8 = 18;
+const TIBRVMSG_U32: u8 = 19;
+const TIBRVMSG_I64: u8 = 20;
+const TIBRVMSG_U64: u8 = 21;
+const TIBRVMSG_F32: u8 = 24;
+const TIBRVMSG_F64: u8 = 25;
+const TIBRVMSG_IPPORT16: u8 = 26;
+const TIBRVMSG_IPADDR32: u8 = 27;
+const TIBRVMSG_I8ARRAY: u8 = 34;
+const TIBRVMSG_U8ARRAY: u8 = 35;
+const TIBRVMSG_I16ARRAY: u8 = 36;
+const TIBRVMSG_U16ARRAY: u8 = 37;
+const TIBRVMSG_I32ARRAY: u8 = 38;
+const TIBRVMSG_U32ARRAY: u8 = 39;
+const TIBRVMSG_I64ARRAY: u8 = 40;
+const TIBRVMSG_U64ARRAY: u8 = 41;
+const TIBRVMSG_F32ARRAY: u8 = 44;
+const TIBRVMSG_F64ARRAY: u8 = 45;
+
+// Size of the header at the start of every message.
+const HEADER_SIZE: usize = 8;
+
+const MAGIC: [u8; 4] = [0x99, 0x55, 0xee, 0xaa];
+// Set in the name size byte when the field has an id.
+const ID_FLAG: u8 = 0x80;
+// The largest name, including its NUL terminator.
+const MAX_NAME_SIZE: usize = 0x7f;
+const SIZE_U16: u8 = 0x79;
+const SIZE_U32: u8 = 0x7a;
+// Days from 0001-01-01 to the Unix epoch.
+const EPOCH_DAYS_FROM_CE: i64 = 719_163;
+const SECONDS_PER_DAY: i64 = 86_400;
+
+/// An owned message, decoded from the wire format.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Message {
+    /// The fields of the message, in the order they were added.
+    pub fields: Vec<Field>,
+}
+
+/// An error decoding or encoding a message.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// The bytes are not a valid message.
+    Corrupt,
+    /// A field has a type which isn't supported.
+    UnknownFieldType(u8),
+    /// A field name is too long or contains a NUL, or a field has an id
+    /// but no name.
+    InvalidName,
+    /// A field or message is too large to encode.
+    InvalidSize,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Corrupt => write!(f, "Message is corrupt"),
+            Error::UnknownFieldType(t) => write!(f, "Unknown field type: {}", t),
+            Error::InvalidName => write!(f, "Invalid field name"),
+            Error::InvalidSize => write!(f, "Field or message too large to encode"),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+/// A single field of a `Message`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Field {
+    pub name: Option<String>,
+    pub id: Option<u16>,
+    pub value: Value,
+}
+
+/// The data held in a `Field`.
+///
+/// This has a variant for each field type Rendezvous supports, matching
+/// `DecodedField` in the `tibrv` crate but holding owned data.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    String(CString),
+    Message(Message),
+    U8(u8),
+    U8Array(Vec<u8>),
+    I8(i8),
+    I8Array(Vec<i8>),
+    U16(u16),
+    U16Array(Vec<u16>),
+    I16(i16),
+    I16Array(Vec<i16>),
+    U32(u32),
+    U32Array(Vec<u32>),
+    I32(i32),
+    I32Array(Vec<i32>),
+    U64(u64),
+    U64Array(Vec<u64>),
+    I64(i64),
+    I64Array(Vec<i64>),
+    F32(f32),
+    F32Array(Vec<f32>),
+    F64(f64),
+    F64Array(Vec<f64>),
+    Bool(bool),
+    DateTime(NaiveDateTime),
+    Ipv4(Ipv4Addr),
+    IpPort(u16),
+    Opaque(Vec<u8>),
+}
+
+impl Message {
+    /// Construct a new message with no fields.
+    pub fn new() -> Self {
+        Message::default()
+    }
+
+    /// Decode a message from bytes in the wire format.
+    ///
+    /// Bytes after the end of the message are ignored. Fails with
+    /// `Error::Corrupt` if the bytes are not a valid message, or
+    /// `Error::UnknownFieldType` if a field has a type which isn't
+    /// supported.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let size = message_size(bytes)?;
+        decode_message(&bytes[..size])
+    }
+
+    /// Encode this message in the wire format.
+    ///
+    /// Fails with `Error::InvalidName` if a field name is too long or
+    /// contains a NUL, or a field has an id but no name.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let mut bytes = Vec::new();
+        encode_message(self, &mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Get the first field with the given name.
+    pub fn get_field_by_name(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|f| f.name.as_deref() == Some(name))
+    }
+
+    /// Get the field with the given id.
+    pub fn get_field_by_id(&self, id: u16) -> Option<&Field> {
+        self.fields.iter().find(|f| f.id == Some(id))
+    }
+}
+
+/// Get the size of the message at the start of `bytes`.
+///
+/// Only the header is decoded, after checking that the whole message is
+/// present. Fails with `Error::Corrupt` if the header is invalid or the
+/// message is truncated.
+pub fn message_size(bytes: &[u8]) -> Result<usize, Error> {
+    if bytes.len() < HEADER_SIZE || bytes[4..HEADER_SIZE] != MAGIC {
+        return Err(Error::Corrupt);
+    }
+    let size = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+    if size < HEADER_SIZE || size > bytes.len() {
+        return Err(Error::Corrupt);
+    }
+    Ok(size)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], Error> {
+        if n > self.bytes.len() {
+            return Err(Error::Corrupt);
+        }
+        let (taken, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        self.take(1).map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        self.take(2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        self.take(4)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn size(&mut self) -> Result<usize, Error> {
+        match self.u8()? {
+            SIZE_U16 => self.u16().map(usize::from),
+            SIZE_U32 => self.u32().map(|s| s as usize),
+            s if s < SIZE_U16 => Ok(usize::from(s)),
+            _ => Err(Error::Corrupt),
+        }
+    }
+}
+
+// Decode a message which fills the whole of `bytes`.
+fn decode_message(bytes: &[u8]) -> Result<Message, Error> {
+    if message_size(bytes)? != bytes.len() {
+        return Err(Error::Corrupt);
+    }
+    let mut reader = Reader {
+        bytes: &bytes[HEADER_SIZE..],
+    };
+    let mut fields = Vec::new();
+    while !reader.bytes.is_empty() {
+        fields.push(decode_field(&mut reader)?);
+    }
+    Ok(Message { fields })
+}
+
+fn decode_field(reader: &mut Reader) -> Result<Field, Error> {
+    let name_size = reader.u8()?;
+    let name = match usize::from(name_size & !ID_FLAG) {
+        0 => None,
+        n => match reader.take(n)?.split_last() {
+            Some((0, name)) => Some(String::from_utf8_lossy(name).into_owned()),
+            _ => return Err(Error::Corrupt),
+        },
+    };
+    let id = if name_size & ID_FLAG != 0 {
+        Some(reader.u16()?)
+    } else {
+        None
+    };
+
+    let type_ = reader.u8()?;
+    let size = reader.size()?;
+    let data = reader.take(size)?;
+    let value = decode_value(type_, data)?;
+    Ok(Field { name, id, value })
+}
+
+macro_rules! decode_scalar {
+    ($data:expr, $ty:ty) => {{
+        let bytes = <[u8; std::mem::size_of::<$ty>()]>::try_from($data).or(Err(Error::Corrupt))?;
+        <$ty>::from_be_bytes(bytes)
+    }};
+}
+
+macro_rules! decode_array {
+    ($data:expr, $ty:ty) => {{
+        const WIDTH: usize = std::mem::size_of::<$ty>();
+        if $data.len() % WIDTH != 0 {
+            return Err(Error::Corrupt);
+        }
+        $data
+            .chunks(WIDTH)
+            .map(|c| <$ty>::from_be_bytes(<[u8; WIDTH]>::try_from(c).unwrap()))
+            .collect()
+    }};
+}
+
+fn decode_value(type_: u8, data: &[u8]) -> Result<Value, Error> {
+    let value = match type_ {
+        TIBRVMSG_STRING => match data.split_last() {
+            Some((0, s)) => Value::String(CString::new(s).or(Err(Error::Corrupt))?),
+            _ => return Err(Error::Corrupt),
+        },
+        TIBRVMSG_MSG => Value::Message(decode_message(data)?),
+        TIBRVMSG_U8 => Value::U8(decode_scalar!(data, u8)),
+        TIBRVMSG_U8ARRAY => Value::U8Array(data.to_vec()),
+        TIBRVMSG_I8 => Value::I8(decode_scalar!(data, i8)),
+        TIBRVMSG_I8ARRAY => Value::I8Array(data.iter().map(|b| *b as i8).collect()),
+        TIBRVMSG_U16 => Value::U16(decode_scalar!(data, u16)),
+        TIBRVMSG_U16ARRAY => Value::U16Array(decode_array!(data, u16)),
+        TIBRVMSG_I16 => Value::I16(decode_scalar!(data, i16)),
+        TIBRVMSG_I16ARRAY => Value::I16Array(decode_array!(data, i16)),
+        TIBRVMSG_U32 => Value::U32(decode_scalar!(data, u32)),
+        TIBRVMSG_U32ARRAY => Value::U32Array(decode_array!(data, u32)),
+        TIBRVMSG_I32 => Value::I32(decode_scalar!(data, i32)),
+        TIBRVMSG_I32ARRAY => Value::I32Array(decode_array!(data, i32)),
+        TIBRVMSG_U64 => Value::U64(decode_scalar!(data, u64)),
+        TIBRVMSG_U64ARRAY => Value::U64Array(decode_array!(data, u64)),
+        TIBRVMSG_I64 => Value::I64(decode_scalar!(data, i64)),
+        TIBRVMSG_I64ARRAY => Value::I64Array(decode_array!(data, i64)),
+        TIBRVMSG_F32 => Value::F32(decode_scalar!(data, f32)),
+        TIBRVMSG_F32ARRAY => Value::F32Array(decode_array!(data, f32)),
+        TIBRVMSG_F64 => Value::F64(decode_scalar!(data, f64)),
+        TIBRVMSG_F64ARRAY => Value::F64Array(decode_array!(data, f64)),
+        TIBRVMSG_BOOL => match data {
+            [b] => Value::Bool(*b != 0),
+            _ => return Err(Error::Corrupt),
+        },
+        TIBRVMSG_DATETIME => Value::DateTime(decode_datetime(data)?),
+        TIBRVMSG_IPADDR32 => Value::Ipv4(Ipv4Addr::from(decode_scalar!(data, u32))),
+        TIBRVMSG_IPPORT16 => Value::IpPort(decode_scalar!(data, u16)),
+        TIBRVMSG_OPAQUE => Value::Opaque(data.to_vec()),
+        _ => return Err(Error::UnknownFieldType(type_)),
+    };
+    Ok(value)
+}
+
+// Datetimes are seconds since the Unix epoch as an `i64`, then
+// nanoseconds as a `u32`.
+fn decode_datetime(data: &[u8]) -> Result<NaiveDateTime, Error> {
+    if data.len() != 12 {
+        return Err(Error::Corrupt);
+    }
+    let sec = decode_scalar!(&data[..8], i64);
+    let nsec = decode_scalar!(&data[8..], u32);
+
+    let days = sec.div_euclid(SECONDS_PER_DAY) + EPOCH_DAYS_FROM_CE;
+    let days = i32::try_from(days).or(Err(Error::Corrupt))?;
+    let secs = sec.rem_euclid(SECONDS_PER_DAY) as u32;
+    NaiveDate::from_num_days_from_ce_opt(days)
+        .and_then(|d| d.and_hms_nano_opt(secs / 3600, secs / 60 % 60, secs % 60, nsec))
+        .ok_or(Error::Corrupt)
+}
+
+fn encode_message(msg: &Message, out: &mut Vec<u8>) -> Result<(), Error> {
+    let start = out.len();
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(&MAGIC);
+    for field in &msg.fields {
+        encode_field(field, out)?;
+    }
+    let size = u32::try_from(out.len() - start).or(Err(Error::InvalidSize))?;
+    out[start..start + 4].copy_from_slice(&size.to_be_bytes());
+    Ok(())
+}
+
+fn encode_field(field: &Field, out: &mut Vec<u8>) -> Result<(), Error> {
+    let name = field.name.as_ref().map_or(&[][..], |n| n.as_bytes());
+    let invalid_name = name.len() >= MAX_NAME_SIZE
+        || name.contains(&0)
+        || (field.name.is_none() && field.id.is_some());
+    if invalid_name {
+        return Err(Error::InvalidName);
+    }
+
+    let flag = if field.id.is_some() { ID_FLAG } else { 0 };
+    match field.name {
+        Some(_) => {
+            out.push((name.len() + 1) as u8 | flag);
+            out.extend_from_slice(name);
+            out.push(0);
+        }
+        None => out.push(0),
+    }
+    if let Some(id) = field.id {
+        out.extend_from_slice(&id.to_be_bytes());
+    }
+
+    let (type_, data) = encode_value(&field.value)?;
+    out.push(type_);
+    encode_size(data.len(), out)?;
+    out.extend_from_slice(&data);
+    Ok(())
+}
+
+fn encode_size(size: usize, out: &mut Vec<u8>) -> Result<(), Error> {
+    if size < usize::from(SIZE_U16) {
+        out.push(size as u8);
+    } else if let Ok(size) = u16::try_from(size) {
+        out.push(SIZE_U16);
+        out.extend_from_slice(&size.to_be_bytes());
+    } else {
+        let size = u32::try_from(size).or(Err(Error::InvalidSize))?;
+        out.push(SIZE_U32);
+        out.extend_from_slice(&size.to_be_bytes());
+    }
+    Ok(())
+}
+
+macro_rules! encode_array {
+    ($values:expr) => {
+        $values.iter().flat_map(|v| v.to_be_bytes()).collect()
+    };
+}
+
+fn encode_value(value: &Value) -> Result<(u8, Vec<u8>), Error> {
+    let encoded = match value {
+        Value::String(s) => (TIBRVMSG_STRING, s.as_bytes_with_nul().to_vec()),
+        Value::Message(m) => {
+            let mut bytes = Vec::new();
+            encode_message(m, &mut bytes)?;
+            (TIBRVMSG_MSG, bytes)
+        }
+        Value::U8(v) => (TIBRVMSG_U8, v.to_be_bytes().to_vec()),
+        Value::U8Array(v) => (TIBRVMSG_U8ARRAY, v.clone()),
+        Value::I8(v) => (TIBRVMSG_I8, v.to_be_bytes().to_vec()),
+        Value::I8Array(v) => (TIBRVMSG_I8ARRAY, encode_array!(v)),
+        Value::U16(v) => (TIBRVMSG_U16, v.to_be_bytes().to_vec()),
+        Value::U16Array(v) => (TIBRVMSG_U16ARRAY, encode_array!(v)),
+        Value::I16(v) => (TIBRVMSG_I16, v.to_be_bytes().to_vec()),
+        Value::I16Array(v) => (TIBRVMSG_I16ARRAY, encode_array!(v)),
+        Value::U32(v) => (TIBRVMSG_U32, v.to_be_bytes().to_vec()),
+        Value::U32Array(v) => (TIBRVMSG_U32ARRAY, encode_array!(v)),
+        Value::I32(v) => (TIBRVMSG_I32, v.to_be_bytes().to_vec()),
+        Value::I32Array(v) => (TIBRVMSG_I32ARRAY, encode_array!(v)),
+        Value::U64(v) => (TIBRVMSG_U64, v.to_be_bytes().to_vec()),
+        Value::U64Array(v) => (TIBRVMSG_U64ARRAY, encode_array!(v)),
+        Value::I64(v) => (TIBRVMSG_I64, v.to_be_bytes().to_vec()),
+        Value::I64Array(v) => (TIBRVMSG_I64ARRAY, encode_array!(v)),
+        Value::F32(v) => (TIBRVMSG_F32, v.to_be_bytes().to_vec()),
+        Value::F32Array(v) => (TIBRVMSG_F32ARRAY, encode_array!(v)),
+        Value::F64(v) => (TIBRVMSG_F64, v.to_be_bytes().to_vec()),
+        Value::F64Array(v) => (TIBRVMSG_F64ARRAY, encode_array!(v)),
+        Value::Bool(v) => (TIBRVMSG_BOOL, vec![u8::from(*v)]),
+        Value::DateTime(v) => {
+            let days = i64::from(v.num_days_from_ce()) - EPOCH_DAYS_FROM_CE;
+            let sec = days * SECONDS_PER_DAY + i64::from(v.num_seconds_from_midnight());
+            let mut bytes = sec.to_be_bytes().to_vec();
+            bytes.extend_from_slice(&v.nanosecond().to_be_bytes());
+            (TIBRVMSG_DATETIME, bytes)
+        }
+        Value::Ipv4(v) => (TIBRVMSG_IPADDR32, v.octets().to_vec()),
+        Value::IpPort(v) => (TIBRVMSG_IPPORT16, v.to_be_bytes().to_vec()),
+        Value::Opaque(v) => (TIBRVMSG_OPAQUE, v.clone()),
+    };
+    Ok(encoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn named(name: &str, value: Value) -> Field {
+        Field {
+            name: Some(name.to_owned()),
+            id: None,
+            value,
+        }
+    }
+
+    fn datetime() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2018, 7, 24)
+            .and_then(|d| d.and_hms_nano_opt(1, 2, 3, 456_789))
+            .unwrap()
+    }
+
+    #[test]
+    fn roundtrip() {
+        let mut msg = Message::new();
+        msg.fields.push(named("short", Value::U8Array(vec![7; 10])));
+        msg.fields
+            .push(named("medium", Value::U8Array(vec![7; 1000])));
+        msg.fields
+            .push(named("long", Value::Opaque(vec![7; 100_000])));
+        msg.fields.push(Field {
+            name: None,
+            id: None,
+            value: Value::DateTime(datetime()),
+        });
+        let before_epoch = NaiveDate::from_ymd_opt(1901, 12, 13)
+            .and_then(|d| d.and_hms_nano_opt(20, 45, 52, 1))
+            .unwrap();
+        msg.fields.push(named("old", Value::DateTime(before_epoch)));
+
+        let bytes = msg.to_bytes().unwrap();
+        assert_eq!(msg, Message::from_bytes(&bytes).unwrap());
+        assert_eq!(Some(&msg.fields[1]), msg.get_field_by_name("medium"));
+    }
+
+    #[test]
+    fn datetime_encoding() {
+        let epoch = NaiveDate::from_ymd_opt(1970, 1, 1)
+            .and_then(|d| d.and_hms_nano_opt(0, 0, 1, 500_000_000))
+            .unwrap();
+        let mut msg = Message::new();
+        msg.fields.push(Field {
+            name: None,
+            id: None,
+            value: Value::DateTime(epoch),
+        });
+        let bytes = msg.to_bytes().unwrap();
+        // One second, then 500ms in nanoseconds.
+        let data = [0, 0, 0, 0, 0, 0, 0, 1, 0x1d, 0xcd, 0x65, 0x00];
+        assert_eq!(&data[..], &bytes[bytes.len() - 12..]);
+        assert_eq!(msg, Message::from_bytes(&bytes).unwrap());
+    }
+
+    #[test]
+    fn empty_message() {
+        let bytes = Message::new().to_bytes().unwrap();
+        assert_eq!(vec![0, 0, 0, 8, 0x99, 0x55, 0xee, 0xaa], bytes);
+        assert!(Message::from_bytes(&bytes).unwrap().fields.is_empty());
+    }
+
+    #[test]
+    fn invalid_names() {
+        let mut msg = Message::new();
+        msg.fields
+            .push(named(&"A".repeat(MAX_NAME_SIZE), Value::Bool(true)));
+        assert_eq!(Err(Error::InvalidName), msg.to_bytes());
+
+        msg.fields[0].name = Some("A\0B".to_owned());
+        assert_eq!(Err(Error::InvalidName), msg.to_bytes());
+
+        msg.fields[0].name = None;
+        msg.fields[0].id = Some(1);
+        assert_eq!(Err(Error::InvalidName), msg.to_bytes());
+    }
+
+    #[test]
+    fn corrupt_bytes() {
+        let mut msg = Message::new();
+        msg.fields.push(named("value", Value::U32(1)));
+        let bytes = msg.to_bytes().unwrap();
+        let kind = |b: &[u8]| Message::from_bytes(b).unwrap_err();
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(Ok(bytes.len()), message_size(&trailing));
+        assert_eq!(Err(Error::Corrupt), message_size(&bytes[..bytes.len() - 1]));
+
+        let corrupt = Error::Corrupt;
+        assert_eq!(corrupt, kind(&bytes[..bytes.len() - 1]));
+        assert_eq!(corrupt, kind(&bytes[..4]));
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[4] = 0;
+        assert_eq!(corrupt, kind(&bad_magic));
+
+        // After the header, name size byte and 6 byte name come the
+        // field type and the size of its data.
+        let mut bad_size = bytes.clone();
+        bad_size[16] = 2;
+        assert_eq!(corrupt, kind(&bad_size));
+
+        let mut bad_type = bytes.clone();
+        bad_type[15] = 99;
+        assert_eq!(Error::UnknownFieldType(99), kind(&bad_type));
+    }
+}